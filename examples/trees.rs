@@ -1,4 +1,4 @@
-use pretty::{BoxAllocator, DocAllocator, DocBuilder};
+use pretty::{docs, BoxAllocator, DocAllocator, DocBuilder};
 use std::io;
 use std::str;
 
@@ -6,7 +6,7 @@ use std::str;
 pub struct Forest<'a>(&'a [Tree<'a>]);
 
 impl<'a> Forest<'a> {
-    fn forest(forest: &'a [Tree<'a>]) -> Forest<'a> {
+    fn new(forest: &'a [Tree<'a>]) -> Forest<'a> {
         Forest(forest)
     }
 
@@ -20,14 +20,16 @@ impl<'a> Forest<'a> {
         D::Doc: Clone,
         A: Clone,
     {
-        if (self.0).len() == 0 {
+        if self.0.is_empty() {
             allocator.nil()
         } else {
-            allocator
-                .text("[")
-                .append(allocator.hardline().append(self.pretty(allocator)).nest(2))
-                .append(allocator.hardline())
-                .append(allocator.text("]"))
+            docs![
+                allocator,
+                "[",
+                docs![allocator, allocator.hardline(), self.pretty(allocator)].nest(2),
+                allocator.hardline(),
+                "]",
+            ]
         }
     }
 
@@ -39,10 +41,7 @@ impl<'a> Forest<'a> {
     {
         let forest = self.0;
         let separator = allocator.text(",").append(allocator.hardline());
-        allocator.intersperse(
-            forest.into_iter().map(|tree| tree.pretty(allocator)),
-            separator,
-        )
+        allocator.intersperse(forest.iter().map(|tree| tree.pretty(allocator)), separator)
     }
 }
 
@@ -63,7 +62,7 @@ impl<'a> Tree<'a> {
     pub fn node_with_forest(node: &str, forest: &'a [Tree<'a>]) -> Tree<'a> {
         Tree {
             node: node.to_string(),
-            forest: Forest::forest(forest),
+            forest: Forest::new(forest),
         }
     }
 
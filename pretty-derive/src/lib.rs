@@ -0,0 +1,290 @@
+//! Derive macro for the [`Pretty`](https://docs.rs/pretty/*/pretty/trait.Pretty.html) trait.
+//! It is re-exported by `pretty` when its `derive` feature is enabled.
+//!
+//! The generated implementation is written for `&'a T` and lays out values like the standard
+//! `Debug` formatting, except that substructures which fit on the current line are kept on a
+//! single line.
+//!
+//! ```rust
+//! use pretty::{Arena, Pretty};
+//!
+//! #[derive(Pretty)]
+//! struct Point {
+//!     x: i32,
+//!     y: i32,
+//! }
+//!
+//! let arena = Arena::<()>::new();
+//! let point = Point { x: 1, y: 2 };
+//! assert_eq!(point.pretty(&arena).1.pretty(80).to_string(), "Point { x: 1, y: 2 }");
+//! assert_eq!(
+//!     point.pretty(&arena).1.pretty(10).to_string(),
+//!     "Point {\n    x: 1,\n    y: 2,\n}"
+//! );
+//! ```
+//!
+//! ## Field attributes
+//!
+//! * `#[pretty(skip)]` omits the field from the output.
+//! * `#[pretty(rename = "name")]` prints the field under a different name.
+//! * `#[pretty(with = "path")]` formats the field with the function at `path` instead of its
+//!   `Pretty` implementation. The function is called as `path(&field, allocator)` and must return a
+//!   `DocBuilder`.
+//!
+//! Every type parameter `T` of the deriving type gets a `&'a T: Pretty<'a, D, A>` bound.
+
+extern crate proc_macro;
+
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote, ToTokens};
+use syn::{
+    parse_macro_input, parse_quote, spanned::Spanned, Data, DeriveInput, Fields, GenericParam,
+    Ident, Lit, Meta, NestedMeta, Path,
+};
+
+/// The indentation used for the fields of a broken struct, tuple or variant
+const INDENT: isize = 4;
+
+#[proc_macro_derive(Pretty, attributes(pretty))]
+pub fn derive_pretty(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream> {
+    let name = &input.ident;
+
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let (pattern, doc) = expand_fields(&quote!(#name), &name.to_string(), &data.fields)?;
+            quote! {
+                match self {
+                    #pattern => #doc,
+                }
+            }
+        }
+        Data::Enum(data) => {
+            let arms = data
+                .variants
+                .iter()
+                .map(|variant| {
+                    let variant_name = &variant.ident;
+                    let (pattern, doc) = expand_fields(
+                        &quote!(#name::#variant_name),
+                        &variant_name.to_string(),
+                        &variant.fields,
+                    )?;
+                    Ok(quote!(#pattern => #doc,))
+                })
+                .collect::<syn::Result<Vec<_>>>()?;
+            if arms.is_empty() {
+                // An enum without variants has no values
+                quote!(match *self {})
+            } else {
+                quote! {
+                    match self {
+                        #(#arms)*
+                    }
+                }
+            }
+        }
+        Data::Union(_) => {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "`Pretty` can not be derived for unions",
+            ))
+        }
+    };
+
+    let (_, ty_generics, _) = input.generics.split_for_impl();
+
+    let mut generics = input.generics.clone();
+    let type_params = generics
+        .type_params()
+        .map(|param| param.ident.clone())
+        .collect::<Vec<_>>();
+    generics
+        .params
+        .insert(0, GenericParam::Lifetime(parse_quote!('__a)));
+    generics.params.push(parse_quote!(__D));
+    generics.params.push(parse_quote!(__A));
+    {
+        let where_clause = generics.make_where_clause();
+        where_clause
+            .predicates
+            .push(parse_quote!(__D: ?Sized + ::pretty::DocAllocator<'__a, __A>));
+        where_clause.predicates.push(parse_quote!(__A: '__a));
+        for param in &type_params {
+            where_clause.predicates.push(parse_quote!(#param: '__a));
+            where_clause
+                .predicates
+                .push(parse_quote!(&'__a #param: ::pretty::Pretty<'__a, __D, __A>));
+        }
+    }
+    let (impl_generics, _, where_clause) = generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::pretty::Pretty<'__a, __D, __A> for &'__a #name #ty_generics
+            #where_clause
+        {
+            fn pretty(self, allocator: &'__a __D) -> ::pretty::DocBuilder<'__a, __D, __A> {
+                #body
+            }
+        }
+    })
+}
+
+#[derive(Default)]
+struct FieldAttrs {
+    skip: bool,
+    rename: Option<String>,
+    with: Option<Path>,
+}
+
+impl FieldAttrs {
+    fn parse(attrs: &[syn::Attribute]) -> syn::Result<FieldAttrs> {
+        let mut field_attrs = FieldAttrs::default();
+        for attr in attrs {
+            if !attr.path.is_ident("pretty") {
+                continue;
+            }
+            let list = match attr.parse_meta()? {
+                Meta::List(list) => list,
+                meta => return Err(syn::Error::new(meta.span(), "expected `#[pretty(...)]`")),
+            };
+            for nested in list.nested {
+                match nested {
+                    NestedMeta::Meta(Meta::Path(ref path)) if path.is_ident("skip") => {
+                        field_attrs.skip = true;
+                    }
+                    NestedMeta::Meta(Meta::NameValue(ref name_value))
+                        if name_value.path.is_ident("rename") =>
+                    {
+                        match &name_value.lit {
+                            Lit::Str(s) => field_attrs.rename = Some(s.value()),
+                            lit => return Err(syn::Error::new(lit.span(), "expected a string")),
+                        }
+                    }
+                    NestedMeta::Meta(Meta::NameValue(ref name_value))
+                        if name_value.path.is_ident("with") =>
+                    {
+                        match &name_value.lit {
+                            Lit::Str(s) => field_attrs.with = Some(s.parse()?),
+                            lit => return Err(syn::Error::new(lit.span(), "expected a string")),
+                        }
+                    }
+                    nested => {
+                        return Err(syn::Error::new(
+                            nested.span(),
+                            "unknown attribute, expected one of `skip`, `rename` or `with`",
+                        ))
+                    }
+                }
+            }
+        }
+        Ok(field_attrs)
+    }
+}
+
+/// Returns the pattern which binds the fields of a struct or variant together with the expression
+/// building the document for it
+fn expand_fields(
+    path: &TokenStream,
+    name: &str,
+    fields: &Fields,
+) -> syn::Result<(TokenStream, TokenStream)> {
+    let mut bindings = Vec::new();
+    let mut docs = Vec::new();
+    for (i, field) in fields.iter().enumerate() {
+        let attrs = FieldAttrs::parse(&field.attrs)?;
+        let binding = if attrs.skip {
+            quote!(_)
+        } else {
+            format_ident!("__field{}", i).into_token_stream()
+        };
+        bindings.push(match &field.ident {
+            Some(ident) => quote!(#ident: #binding),
+            None => binding.clone(),
+        });
+
+        if attrs.skip {
+            continue;
+        }
+        let value = match &attrs.with {
+            Some(with) => quote!(#with(#binding, allocator)),
+            None => quote!(::pretty::Pretty::pretty(#binding, allocator)),
+        };
+        docs.push(match &field.ident {
+            Some(ident) => {
+                let label = format!(
+                    "{}: ",
+                    attrs.rename.unwrap_or_else(|| unraw(ident).to_string())
+                );
+                quote!(::pretty::DocAllocator::text(allocator, #label).append(#value))
+            }
+            None => value,
+        });
+    }
+
+    let (pattern, delimited) = match fields {
+        Fields::Named(_) => (
+            quote!(#path { #(#bindings,)* }),
+            quote! {
+                .append(" ")
+                .append(
+                    ::pretty::DocAllocator::delimited(
+                        allocator,
+                        "{",
+                        ",",
+                        "}",
+                        ::pretty::TrailingComma::IfBroken,
+                    )
+                    .nest(#INDENT)
+                    .padded()
+                    .build(vec![#(#docs),*])
+                )
+            },
+        ),
+        Fields::Unnamed(_) => (
+            quote!(#path( #(#bindings,)* )),
+            quote! {
+                .append(
+                    ::pretty::DocAllocator::delimited(
+                        allocator,
+                        "(",
+                        ",",
+                        ")",
+                        ::pretty::TrailingComma::IfBroken,
+                    )
+                    .nest(#INDENT)
+                    .build(vec![#(#docs),*])
+                )
+            },
+        ),
+        Fields::Unit => (path.clone(), quote!()),
+    };
+
+    if docs.is_empty() {
+        return Ok((
+            pattern,
+            quote!(::pretty::DocAllocator::text(allocator, #name)),
+        ));
+    }
+
+    let doc = quote! {
+        ::pretty::DocAllocator::text(allocator, #name)
+            #delimited
+    };
+    Ok((pattern, doc))
+}
+
+/// Strips the `r#` prefix from raw identifiers
+fn unraw(ident: &Ident) -> Ident {
+    let s = ident.to_string();
+    match s.strip_prefix("r#") {
+        Some(s) => Ident::new(s, ident.span()),
+        None => ident.clone(),
+    }
+}
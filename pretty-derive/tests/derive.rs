@@ -0,0 +1,166 @@
+use pretty::{Arena, DocAllocator, DocBuilder, Pretty};
+
+macro_rules! test {
+    ($size:expr, $actual:expr, $expected:expr) => {
+        let actual = &$actual;
+        let arena = Arena::<()>::new();
+        let doc = actual.pretty(&arena);
+        assert_eq!(doc.1.pretty($size).to_string(), $expected);
+    };
+    ($actual:expr, $expected:expr) => {
+        test!(70, $actual, $expected)
+    };
+}
+
+#[derive(Pretty)]
+struct Point {
+    x: String,
+    y: String,
+}
+
+#[derive(Pretty)]
+struct Wrapper(String, String);
+
+#[derive(Pretty)]
+struct Unit;
+
+#[derive(Pretty)]
+struct Generic<T> {
+    value: T,
+}
+
+#[derive(Pretty)]
+enum Expr {
+    Var(String),
+    Call {
+        name: String,
+        #[pretty(with = "args")]
+        args: Vec<Expr>,
+    },
+    Hole,
+}
+
+#[derive(Pretty)]
+enum Empty {}
+
+fn number<'a, D, A>(n: &u32, allocator: &'a D) -> DocBuilder<'a, D, A>
+where
+    D: ?Sized + DocAllocator<'a, A>,
+{
+    allocator.as_string(n)
+}
+
+fn args<'a, D, A>(args: &'a [Expr], allocator: &'a D) -> DocBuilder<'a, D, A>
+where
+    D: ?Sized + DocAllocator<'a, A>,
+    A: 'a,
+{
+    allocator
+        .concat(
+            args.iter()
+                .map(|arg| allocator.text("-").append(arg.pretty(allocator))),
+        )
+        .brackets()
+}
+
+#[derive(Pretty)]
+struct Attributes {
+    #[pretty(rename = "renamed")]
+    name: String,
+    #[pretty(skip)]
+    #[allow(dead_code)]
+    skipped: String,
+    #[pretty(with = "number")]
+    count: u32,
+    #[pretty(with = "args")]
+    r#type: Vec<Expr>,
+}
+
+#[test]
+fn named_struct() {
+    let point = Point {
+        x: "1".into(),
+        y: "2".into(),
+    };
+    test!(point, r#"Point { x: "1", y: "2" }"#);
+    test!(10, point, "Point {\n    x: \"1\",\n    y: \"2\",\n}");
+}
+
+#[test]
+fn tuple_struct() {
+    let wrapper = Wrapper("first".into(), "second".into());
+    test!(wrapper, r#"Wrapper("first", "second")"#);
+    test!(10, wrapper, "Wrapper(\n    \"first\",\n    \"second\",\n)");
+}
+
+#[test]
+fn unit_struct() {
+    test!(Unit, "Unit");
+}
+
+#[test]
+fn empty_enum() {
+    fn assert_pretty<'a, T: Pretty<'a, Arena<'a, ()>>>() {}
+    assert_pretty::<&Empty>();
+}
+
+#[test]
+fn generic_struct() {
+    let generic = Generic {
+        value: Wrapper("a".into(), "b".into()),
+    };
+    test!(generic, r#"Generic { value: Wrapper("a", "b") }"#);
+}
+
+#[test]
+fn enum_variants() {
+    let expr = Expr::Call {
+        name: "f".into(),
+        args: vec![Expr::Var("x".into()), Expr::Hole],
+    };
+    test!(Expr::Hole, "Hole");
+    test!(Expr::Var("x".into()), r#"Var("x")"#);
+    test!(expr, r#"Call { name: "f", args: [-Var("x")-Hole] }"#);
+    test!(
+        30,
+        expr,
+        "Call {\n    name: \"f\",\n    args: [-Var(\"x\")-Hole],\n}"
+    );
+}
+
+#[test]
+fn std_fields() {
+    let generic = Generic {
+        value: (vec![1, 2], Some("a")),
+    };
+    test!(generic, r#"Generic { value: ([1, 2], Some("a")) }"#);
+}
+
+#[test]
+fn nested_groups_stay_flat() {
+    let point = Generic {
+        value: Point {
+            x: "1".into(),
+            y: "2".into(),
+        },
+    };
+    test!(
+        40,
+        point,
+        "Generic {\n    value: Point { x: \"1\", y: \"2\" },\n}"
+    );
+}
+
+#[test]
+fn field_attributes() {
+    let attributes = Attributes {
+        name: "name".into(),
+        skipped: "skipped".into(),
+        count: 3,
+        r#type: vec![Expr::Hole, Expr::Var("x".into())],
+    };
+    test!(
+        attributes,
+        r#"Attributes { renamed: "name", count: 3, type: [-Hole-Var("x")] }"#
+    );
+}
@@ -0,0 +1,341 @@
+//! Width aware formatting of `fmt::Debug` values.
+//!
+//! `fmt::Formatter` can not be constructed outside of the standard library so instead the compact
+//! (`{:?}`) output is parsed back into nested groups. Each parenthesized, bracketed or braced
+//! sequence becomes a group which is laid out like `{:#?}` when it does not fit on the line.
+
+use std::fmt;
+
+use crate::{Arena, DocAllocator, DocBuilder, TrailingComma};
+
+/// Formats `value` with its `fmt::Debug` implementation, breaking only the substructures which do
+/// not fit in `width` columns.
+///
+/// When everything fits the result is identical to `format!("{:?}", value)` and when nothing fits
+/// it is identical to `format!("{:#?}", value)` (for `Debug` implementations that use the
+/// `debug_*` builders of `fmt::Formatter`).
+///
+/// ```
+/// #[derive(Debug)]
+/// struct Point {
+///     x: i32,
+///     y: i32,
+/// }
+///
+/// let points = vec![Point { x: 1, y: 2 }, Point { x: 3, y: 4 }];
+/// assert_eq!(pretty::debug(&points, 80), "[Point { x: 1, y: 2 }, Point { x: 3, y: 4 }]");
+/// assert_eq!(
+///     pretty::debug(&points, 30),
+///     "[\n    Point { x: 1, y: 2 },\n    Point { x: 3, y: 4 },\n]"
+/// );
+/// ```
+pub fn debug<T>(value: &T, width: usize) -> String
+where
+    T: ?Sized + fmt::Debug,
+{
+    let arena = Arena::<()>::new();
+    let doc = debug_doc(&arena, value);
+    doc.1.pretty(width).to_string()
+}
+
+/// Builds a document from the `fmt::Debug` output of `value`. See [`debug`](fn.debug.html).
+pub fn debug_doc<'a, D, A, T>(allocator: &'a D, value: &T) -> DocBuilder<'a, D, A>
+where
+    D: ?Sized + DocAllocator<'a, A>,
+    A: 'a,
+    T: ?Sized + fmt::Debug,
+{
+    let text = format!("{:?}", value);
+    let mut parser = Parser {
+        allocator,
+        input: &text,
+        pos: 0,
+    };
+    match parser.items(None) {
+        Some(doc) => doc,
+        // Not something we know how to split, output the text as is
+        None => allocator.text(text),
+    }
+}
+
+struct Parser<'a, 's, D: ?Sized> {
+    allocator: &'a D,
+    input: &'s str,
+    pos: usize,
+}
+
+/// How a sequence of items ended
+struct Items<'a, D, A>
+where
+    D: ?Sized + DocAllocator<'a, A>,
+{
+    docs: Vec<DocBuilder<'a, D, A>>,
+    /// The sequence ended with a `,` (as in `(1,)`)
+    trailing_comma: bool,
+}
+
+impl<'a, 's, D> Parser<'a, 's, D>
+where
+    D: ?Sized,
+{
+    fn rest(&self) -> &'s str {
+        &self.input[self.pos..]
+    }
+
+    fn text<A>(&self, start: usize) -> DocBuilder<'a, D, A>
+    where
+        D: DocAllocator<'a, A>,
+        A: 'a,
+    {
+        self.allocator.text(self.input[start..self.pos].to_string())
+    }
+
+    /// Parses the whole input, returning `None` if the delimiters are unbalanced
+    fn items<A>(&mut self, close: Option<&'static str>) -> Option<DocBuilder<'a, D, A>>
+    where
+        D: DocAllocator<'a, A>,
+        A: 'a,
+    {
+        let items = self.sequence(close)?;
+        let mut docs = items.docs.into_iter();
+        let first = docs.next()?;
+        Some(docs.fold(first, |acc, doc| acc.append(", ").append(doc)))
+    }
+
+    /// Parses `, ` separated items until `close` (or the end of input if `close` is `None`) is
+    /// reached. `close` itself is not consumed.
+    fn sequence<A>(&mut self, close: Option<&'static str>) -> Option<Items<'a, D, A>>
+    where
+        D: DocAllocator<'a, A>,
+        A: 'a,
+    {
+        let mut docs = Vec::new();
+        loop {
+            let item = self.item(close)?;
+            docs.push(item);
+            let rest = self.rest();
+            if rest.starts_with(", ") {
+                self.pos += 2;
+            } else if rest.starts_with(',') && close.is_some_and(|c| rest[1..].starts_with(c)) {
+                self.pos += 1;
+                return Some(Items {
+                    docs,
+                    trailing_comma: true,
+                });
+            } else {
+                return Some(Items {
+                    docs,
+                    trailing_comma: false,
+                });
+            }
+        }
+    }
+
+    /// Parses a single item, stopping at a `, ` separator or at `close`
+    fn item<A>(&mut self, close: Option<&'static str>) -> Option<DocBuilder<'a, D, A>>
+    where
+        D: DocAllocator<'a, A>,
+        A: 'a,
+    {
+        let mut doc = self.allocator.nil();
+        let mut start = self.pos;
+        loop {
+            let rest = self.rest();
+            let c = match rest.chars().next() {
+                Some(c) => c,
+                None if close.is_none() => break,
+                None => return None,
+            };
+            if close.is_some_and(|close| rest.starts_with(close)) || rest.starts_with(", ") {
+                break;
+            }
+            if rest.starts_with(',') && close.is_some_and(|c| rest[1..].starts_with(c)) {
+                break;
+            }
+            match c {
+                '(' | '[' | '{' => {
+                    doc = doc.append(self.text(start));
+                    doc = doc.append(self.group(c)?);
+                    start = self.pos;
+                    continue;
+                }
+                ')' | ']' | '}' => return None,
+                '"' => self.skip_string()?,
+                '\'' => self.skip_char(),
+                _ => self.pos += c.len_utf8(),
+            }
+        }
+        Some(doc.append(self.text(start)))
+    }
+
+    /// Parses a delimited group starting at `open`
+    fn group<A>(&mut self, open: char) -> Option<DocBuilder<'a, D, A>>
+    where
+        D: DocAllocator<'a, A>,
+        A: 'a,
+    {
+        let allocator = self.allocator;
+        self.pos += open.len_utf8();
+        let rest = self.rest();
+
+        // Structs are printed as `Name { field: value }` with spaces inside the braces, maps and
+        // sets as `{key: value}`
+        let (close, spaced): (&'static str, _) = match open {
+            '(' => (")", false),
+            '[' => ("]", false),
+            _ if rest.starts_with(' ') => (" }", true),
+            _ => ("}", false),
+        };
+
+        let empty_close = if spaced { &close[1..] } else { close };
+        if rest.starts_with(empty_close) {
+            self.pos += empty_close.len();
+            let start = self.pos - open.len_utf8() - empty_close.len();
+            return Some(self.text(start));
+        }
+        if spaced {
+            self.pos += 1;
+        }
+
+        let items = self.sequence(Some(close))?;
+        self.pos += close.len();
+
+        let trailing_comma = if items.trailing_comma {
+            TrailingComma::Always
+        } else {
+            TrailingComma::IfBroken
+        };
+        let delimited = allocator.delimited(
+            allocator.as_string(open),
+            ",",
+            close.trim_start(),
+            trailing_comma,
+        );
+        Some(if spaced {
+            delimited.padded().build(items.docs)
+        } else {
+            delimited.build(items.docs)
+        })
+    }
+
+    /// Skips over a string literal, handling escaped quotes
+    fn skip_string(&mut self) -> Option<()> {
+        let mut escaped = false;
+        for (i, c) in self.rest().char_indices().skip(1) {
+            match c {
+                '\\' if !escaped => escaped = true,
+                '"' if !escaped => {
+                    self.pos += i + 1;
+                    return Some(());
+                }
+                _ => escaped = false,
+            }
+        }
+        None
+    }
+
+    /// Skips over a character literal. Since a `'` may also appear on its own (for instance in a
+    /// lifetime), it is treated as a plain character unless a closing `'` follows shortly.
+    fn skip_char(&mut self) {
+        let rest = self.rest();
+        let len = if rest[1..].starts_with('\\') {
+            rest.get(3..)
+                .and_then(|escaped| escaped.find('\''))
+                .map(|i| i + 4)
+        } else {
+            rest[1..]
+                .chars()
+                .next()
+                .filter(|_| rest[1..].chars().nth(1) == Some('\''))
+                .map(|c| c.len_utf8() + 2)
+        };
+        self.pos += len.unwrap_or(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::BTreeMap;
+
+    #[allow(dead_code)]
+    #[derive(Debug)]
+    struct Point {
+        x: i32,
+        y: i32,
+    }
+
+    #[derive(Debug)]
+    struct Unit;
+
+    #[allow(dead_code)]
+    #[derive(Debug)]
+    struct Tuple(&'static str, char);
+
+    #[allow(dead_code)]
+    #[derive(Debug)]
+    enum Tree {
+        Leaf(i32),
+        Node(Vec<Tree>),
+    }
+
+    macro_rules! test {
+        ($size:expr, $actual:expr, $expected:expr) => {
+            difference::assert_diff!(&debug(&$actual, $size), $expected, "\n", 0);
+        };
+    }
+
+    // Formatting with no width should break everything, just like `{:#?}`, while an infinite width
+    // should reproduce `{:?}`
+    macro_rules! test_extremes {
+        ($value:expr) => {
+            let value = $value;
+            test!(0, value, &format!("{:#?}", value));
+            test!(usize::MAX, value, &format!("{:?}", value));
+        };
+    }
+
+    #[test]
+    fn matches_std_formatting() {
+        test_extremes!(1);
+        test_extremes!(Unit);
+        test_extremes!(Point { x: 1, y: -2 });
+        test_extremes!(Tuple("a, b", ','));
+        test_extremes!(vec![Some(1), None]);
+        test_extremes!(Vec::<i32>::new());
+        test_extremes!((1,));
+        test_extremes!((1, "(", ']'));
+        test_extremes!(vec![(1, 'a')].into_iter().collect::<BTreeMap<_, _>>());
+        test_extremes!(Tree::Node(vec![
+            Tree::Leaf(1),
+            Tree::Node(vec![Tree::Leaf(2), Tree::Leaf(3)]),
+        ]));
+        test_extremes!("quote \" inside");
+        test_extremes!('\'');
+    }
+
+    #[test]
+    fn small_substructures_stay_flat() {
+        let tree = Tree::Node(vec![
+            Tree::Leaf(1),
+            Tree::Node(vec![Tree::Leaf(2), Tree::Leaf(3)]),
+        ]);
+        test!(
+            36,
+            tree,
+            "Node(\n    [\n        Leaf(1),\n        Node([Leaf(2), Leaf(3)]),\n    ],\n)"
+        );
+    }
+
+    #[test]
+    fn unbalanced_output_is_kept_as_is() {
+        struct Unbalanced;
+        impl fmt::Debug for Unbalanced {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "[a, b, (c")
+            }
+        }
+        test!(1, Unbalanced, "[a, b, (c");
+    }
+}
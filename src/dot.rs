@@ -0,0 +1,108 @@
+//! Export of the structure of a document as a Graphviz DOT graph.
+
+use std::{collections::HashMap, fmt, fmt::Write};
+
+use crate::{dump::label, Doc, DocPtr};
+
+/// Escapes `s` for use in a quoted DOT string
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+pub(crate) fn to_dot<'d, 'a, T, A>(doc: &'d Doc<'a, T, A>) -> String
+where
+    T: DocPtr<'a, A>,
+    A: fmt::Debug,
+{
+    let mut out = String::new();
+    out.push_str("digraph doc {\n    node [shape=box, fontname=\"monospace\"];\n");
+
+    // Documents which are shared in an arena (by `RefDoc`) have the same address and are only
+    // written once. `BoxDoc` and `RcDoc` copy their children, which are therefore written again.
+    let mut ids: HashMap<*const Doc<'a, T, A>, usize> = HashMap::new();
+    ids.insert(doc, 0);
+    let mut stack = vec![doc];
+    while let Some(doc) = stack.pop() {
+        let node = ids[&(doc as *const _)];
+        writeln!(out, "    n{} [label=\"{}\"];", node, escape(&label(doc))).unwrap();
+
+        let children: Vec<(&'d Doc<'a, T, A>, Option<&str>)> = match *doc {
+            Doc::Append(ref l, ref r) => vec![(l, None), (r, None)],
+            Doc::FlatAlt(ref b, ref f) => vec![(b, Some("break")), (f, Some("flat"))],
+            Doc::Union(ref l, ref r) => vec![(l, Some("left")), (r, Some("right"))],
+            Doc::Group(ref doc) | Doc::Nest(_, ref doc) | Doc::Annotated(_, ref doc) => {
+                vec![(doc, None)]
+            }
+            _ => Vec::new(),
+        };
+        let mut unseen = Vec::new();
+        for (child, edge) in children {
+            let next = ids.len();
+            let child_id = *ids.entry(child).or_insert_with(|| {
+                unseen.push(child);
+                next
+            });
+            match edge {
+                Some(edge) => writeln!(out, "    n{} -> n{} [label=\"{}\"];", node, child_id, edge),
+                None => writeln!(out, "    n{} -> n{};", node, child_id),
+            }
+            .unwrap();
+        }
+        // Visit the children in document order
+        stack.extend(unseen.into_iter().rev());
+    }
+
+    out.push_str("}\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Arena, DocAllocator};
+
+    #[test]
+    fn shared_nodes_are_written_once() {
+        let arena = Arena::<&str>::new();
+        let shared = arena.text("say \"hi\"").annotate("quote").into_doc();
+        let doc = arena
+            .nil()
+            .append(shared)
+            .append(arena.line())
+            .append(shared)
+            .group();
+        difference::assert_diff!(
+            &doc.1.to_dot(),
+            r#"digraph doc {
+    node [shape=box, fontname="monospace"];
+    n0 [label="Group"];
+    n0 -> n1;
+    n1 [label="Append"];
+    n1 -> n2;
+    n1 -> n3;
+    n2 [label="Append"];
+    n2 -> n3;
+    n2 -> n4;
+    n4 [label="FlatAlt"];
+    n4 -> n5 [label="break"];
+    n4 -> n6 [label="flat"];
+    n5 [label="Line"];
+    n6 [label="Text(\" \")"];
+    n3 [label="Annotated(\"quote\")"];
+    n3 -> n7;
+    n7 [label="Text(\"say \\\"hi\\\"\")"];
+}
+"#,
+            "\n",
+            0
+        );
+    }
+}
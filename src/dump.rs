@@ -0,0 +1,129 @@
+//! An indented tree view of the structure of a document.
+
+use std::fmt;
+
+use crate::{closure_address, Doc, DocPtr};
+
+/// Returns the children of `doc`. Nested `Append` nodes are shown as a single `Append` node with
+/// all of the appended documents as children.
+fn children<'d, 'a, T, A>(doc: &'d Doc<'a, T, A>) -> Vec<&'d Doc<'a, T, A>>
+where
+    T: DocPtr<'a, A>,
+{
+    match *doc {
+        Doc::Append(..) => {
+            let mut children = Vec::new();
+            let mut stack = vec![doc];
+            while let Some(doc) = stack.pop() {
+                match *doc {
+                    Doc::Append(ref l, ref r) => {
+                        stack.push(r);
+                        stack.push(l);
+                    }
+                    _ => children.push(doc),
+                }
+            }
+            children
+        }
+        Doc::FlatAlt(ref l, ref r) | Doc::Union(ref l, ref r) => vec![l, r],
+        Doc::Group(ref doc) | Doc::Nest(_, ref doc) | Doc::Annotated(_, ref doc) => vec![doc],
+        _ => Vec::new(),
+    }
+}
+
+/// Returns the variant of `doc` together with its text, annotation, offset or closure address
+pub(crate) fn label<'a, T, A>(doc: &Doc<'a, T, A>) -> String
+where
+    T: DocPtr<'a, A>,
+    A: fmt::Debug,
+{
+    match *doc {
+        Doc::Nil => "Nil".to_string(),
+        Doc::Append(..) => "Append".to_string(),
+        Doc::FlatAlt(..) => "FlatAlt".to_string(),
+        Doc::Union(..) => "Union".to_string(),
+        Doc::Group(_) => "Group".to_string(),
+        Doc::Nest(offset, _) => format!("Nest({})", offset),
+        Doc::Annotated(ref ann, _) => format!("Annotated({:?})", ann),
+        Doc::Line => "Line".to_string(),
+        Doc::OwnedText(ref s) => format!("Text({:?})", s),
+        Doc::BorrowedText(s) => format!("Text({:?})", s),
+        Doc::SmallText(ref s) => format!("Text({:?})", &s[..]),
+        Doc::Column(ref f) => format!("Column({:?})", closure_address(&**f)),
+        Doc::Nesting(ref f) => format!("Nesting({:?})", closure_address(&**f)),
+        Doc::PageWidth(ref f) => format!("PageWidth({:?})", closure_address(&**f)),
+        Doc::Lazy(ref f) => format!("Lazy({:?})", closure_address(&**f)),
+    }
+}
+
+/// Shows each node of `doc` on its own line with its children indented below it
+pub(crate) fn dump<'a, T, A>(doc: &Doc<'a, T, A>) -> String
+where
+    T: DocPtr<'a, A>,
+    A: fmt::Debug,
+{
+    let mut out = String::new();
+    // Documents may be too deep to visit recursively
+    let mut stack = vec![(doc, 0)];
+    while let Some((doc, depth)) = stack.pop() {
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        for _ in 0..depth {
+            out.push_str("  ");
+        }
+        out.push_str(&label(doc));
+        stack.extend(
+            children(doc)
+                .into_iter()
+                .rev()
+                .map(|child| (child, depth + 1)),
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Arena, BoxDoc, DocAllocator};
+
+    #[test]
+    fn dump_tree() {
+        let arena = Arena::<&str>::new();
+        let doc = arena
+            .text("a")
+            .append(arena.line())
+            .append(arena.text(String::from("b")).annotate("bold"))
+            .append(arena.text("c").nest(2))
+            .group();
+        difference::assert_diff!(
+            &doc.1.dump(),
+            r#"Group
+  Append
+    Text("a")
+    FlatAlt
+      Line
+      Text(" ")
+    Annotated("bold")
+      Text("b")
+    Nest(2)
+      Text("c")"#,
+            "\n",
+            0
+        );
+    }
+
+    #[test]
+    fn dump_deep_document() {
+        let mut doc = BoxDoc::<()>::text("x");
+        for _ in 0..5000 {
+            doc = doc.group();
+        }
+        let dump = doc.dump();
+        assert_eq!(dump.lines().count(), 5001);
+        assert_eq!(
+            dump.lines().last(),
+            Some(&*format!("{}Text(\"x\")", "  ".repeat(5000)))
+        );
+    }
+}
@@ -0,0 +1,161 @@
+//! Elision of deeply nested groups.
+
+use crate::{Doc, DocAllocator, DocPtr};
+
+/// Whether `doc` is a single line break, as in the groups created by `softline` and `softline_`
+fn is_line<'a, T, A>(doc: &Doc<'a, T, A>) -> bool
+where
+    T: DocPtr<'a, A>,
+{
+    match *doc {
+        Doc::Line => true,
+        Doc::FlatAlt(ref b, ref f) => {
+            matches!(**b, Doc::Line) && (matches!(**f, Doc::Nil) || f.as_text().is_some())
+        }
+        _ => false,
+    }
+}
+
+/// Returns a copy of `doc` where every group nested inside `depth` other groups is replaced by
+/// `placeholder`
+pub(crate) fn elide_below<'a, D, A>(
+    allocator: &'a D,
+    doc: &D::Doc,
+    depth: usize,
+    placeholder: &D::Doc,
+) -> D::Doc
+where
+    D: ?Sized + DocAllocator<'a, A>,
+    D::Doc: Clone,
+    A: Clone + 'a,
+{
+    match **doc {
+        // Groups of a single line break (`softline`) do not contain anything to elide
+        Doc::Group(ref inner) if is_line(inner) => doc.clone(),
+        Doc::Group(ref inner) => match depth.checked_sub(1) {
+            Some(depth) => allocator.alloc(Doc::Group(elide_below(
+                allocator,
+                inner,
+                depth,
+                placeholder,
+            ))),
+            None => placeholder.clone(),
+        },
+        Doc::Append(..) => {
+            // Long sequences are built by appending to the left so walk that side iteratively
+            let mut rights = Vec::new();
+            let mut left = doc;
+            while let Doc::Append(ref l, ref r) = **left {
+                rights.push(r);
+                left = l;
+            }
+            let mut result = elide_below(allocator, left, depth, placeholder);
+            for right in rights.into_iter().rev() {
+                let right = elide_below(allocator, right, depth, placeholder);
+                result = allocator.alloc(Doc::Append(result, right));
+            }
+            result
+        }
+        Doc::FlatAlt(ref b, ref f) => allocator.alloc(Doc::FlatAlt(
+            elide_below(allocator, b, depth, placeholder),
+            elide_below(allocator, f, depth, placeholder),
+        )),
+        Doc::Union(ref l, ref r) => allocator.alloc(Doc::Union(
+            elide_below(allocator, l, depth, placeholder),
+            elide_below(allocator, r, depth, placeholder),
+        )),
+        Doc::Nest(offset, ref inner) => allocator.alloc(Doc::Nest(
+            offset,
+            elide_below(allocator, inner, depth, placeholder),
+        )),
+        Doc::Annotated(ref ann, ref inner) => allocator.alloc(Doc::Annotated(
+            ann.clone(),
+            elide_below(allocator, inner, depth, placeholder),
+        )),
+        Doc::Column(ref f) => {
+            let f = f.clone();
+            let placeholder = placeholder.clone();
+            allocator
+                .column(move |column| elide_below(allocator, &f(column), depth, &placeholder))
+                .into_doc()
+        }
+        Doc::Nesting(ref f) => {
+            let f = f.clone();
+            let placeholder = placeholder.clone();
+            allocator
+                .nesting(move |indent| elide_below(allocator, &f(indent), depth, &placeholder))
+                .into_doc()
+        }
+        Doc::PageWidth(ref f) => {
+            let f = f.clone();
+            let placeholder = placeholder.clone();
+            allocator
+                .page_width(move |width| elide_below(allocator, &f(width), depth, &placeholder))
+                .into_doc()
+        }
+        Doc::Lazy(ref f) => {
+            let f = f.clone();
+            let placeholder = placeholder.clone();
+            let f = allocator.alloc_column_fn(move |width| {
+                elide_below(allocator, &f(width), depth, &placeholder)
+            });
+            allocator.alloc(Doc::Lazy(f))
+        }
+        Doc::Nil | Doc::Line | Doc::OwnedText(_) | Doc::BorrowedText(_) | Doc::SmallText(_) => {
+            doc.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Arena, DocAllocator, Pretty};
+
+    macro_rules! test {
+        ($size:expr, $actual:expr, $expected:expr) => {
+            let mut s = String::new();
+            $actual.render_fmt($size, &mut s).unwrap();
+            difference::assert_diff!(&s, $expected, "\n", 0);
+        };
+    }
+
+    #[test]
+    fn elide_nested_groups() {
+        let arena = Arena::<()>::new();
+        let value = vec![vec![vec![1, 2]], vec![vec![3]]];
+        let doc = || value.pretty(&arena);
+
+        test!(80, doc().elide_below(3, "…").1, "[[[1, 2]], [[3]]]");
+        test!(80, doc().elide_below(2, "[…]").1, "[[[…]], [[…]]]");
+        test!(80, doc().elide_below(1, "[…]").1, "[[…], […]]");
+        test!(80, doc().elide_below(0, "[…]").1, "[…]");
+        test!(5, doc().elide_below(1, "[…]").1, "[\n    […],\n    […],\n]");
+    }
+
+    #[test]
+    fn elide_keeps_softlines() {
+        let arena = Arena::<()>::new();
+        let doc = arena
+            .reflow("a b")
+            .append(arena.text("c").append(arena.softline()).group())
+            .elide_below(0, "…");
+        test!(80, doc.1, "a b…");
+    }
+
+    #[test]
+    fn elide_flat_alt_groups() {
+        let arena = Arena::<()>::new();
+        let doc = || {
+            arena
+                .text("a")
+                .append(arena.text("b").group())
+                .append(arena.hardline())
+                .append("c")
+                .flat_alt("z")
+                .group()
+        };
+        test!(0, doc().elide_below(0, "…").1, "…");
+        test!(0, doc().elide_below(1, "…").1, "a…\nc");
+        test!(0, doc().elide_below(2, "…").1, "ab\nc");
+    }
+}
@@ -0,0 +1,126 @@
+//! A view of the rendered output annotated with the layout decisions made on each line.
+
+use crate::{
+    render::{self, Choice, Decision, DecisionKind, RenderOptions},
+    Doc, DocPtr,
+};
+
+/// Describes `decision` for a document rendered in `width` columns
+fn describe(decision: &Decision, width: usize) -> String {
+    let kind = match decision.kind {
+        DecisionKind::Group => "group",
+        DecisionKind::Union => "union",
+    };
+    let choice = match decision.choice {
+        Choice::Flat => "flat",
+        Choice::Break => "break",
+        Choice::Left => "left",
+        Choice::Right => "right",
+    };
+    match decision.failed_at {
+        None => format!(
+            "{} {}: fits, {} columns left",
+            kind, choice, decision.remaining
+        ),
+        Some(column) if column > width => {
+            format!(
+                "{} {}: reached column {} of {}",
+                kind, choice, column, width
+            )
+        }
+        Some(column) => format!("{} {}: line break at column {}", kind, choice, column),
+    }
+}
+
+pub(crate) fn explain<'a, T, A>(doc: &Doc<'a, T, A>, width: usize) -> String
+where
+    T: DocPtr<'a, A> + 'a,
+{
+    let mut output = String::new();
+    let mut decisions = Vec::new();
+    let result = render::traced(
+        doc,
+        &RenderOptions::new(width),
+        &mut output,
+        &mut |decision| decisions.push(decision.clone()),
+    );
+    if let Err(never) = result {
+        match never {}
+    }
+
+    let mut explained = String::new();
+    let mut decisions = decisions.iter().peekable();
+    for (i, line) in output.split('\n').enumerate() {
+        if i != 0 {
+            explained.push('\n');
+        }
+        explained.push_str(line);
+        while let Some(decision) = decisions.next_if(|decision| decision.line == i) {
+            // The column of the decision counts bytes, the caret is placed under the character
+            // starting at that byte
+            let column = line
+                .get(..decision.column)
+                .map_or(decision.column, |before| before.chars().count());
+            explained.push('\n');
+            explained.push_str(&" ".repeat(column));
+            explained.push_str("^ ");
+            explained.push_str(&describe(decision, width));
+        }
+    }
+    explained
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Arena, DocAllocator};
+
+    #[test]
+    fn explain_decisions() {
+        let arena = Arena::<()>::new();
+        let call = |name: &'static str, args: Vec<&'static str>| {
+            arena.text(name).append(
+                arena
+                    .line_()
+                    .append(arena.intersperse(args, arena.text(",").append(arena.line())))
+                    .nest(4)
+                    .append(arena.line_())
+                    .parens()
+                    .group(),
+            )
+        };
+        let doc = call("f", vec!["first", "second"])
+            .append(arena.hardline())
+            .append(call("g", vec!["a"]))
+            .append(arena.hardline())
+            .append(arena.text("x").append(arena.hardline()).append("y").group());
+        difference::assert_diff!(
+            &doc.1.explain(12),
+            r#"f(
+ ^ group break: reached column 15 of 12
+    first,
+    second
+)
+g(a)
+ ^ group flat: fits, 11 columns left
+x
+^ group break: line break at column 1
+y"#,
+            "\n",
+            0
+        );
+    }
+
+    #[test]
+    fn caret_under_non_ascii_text() {
+        let arena = Arena::<()>::new();
+        let doc = arena
+            .text("é→")
+            .append(arena.text("a").append(arena.line()).append("b").group());
+        difference::assert_diff!(
+            &doc.1.explain(80),
+            "é→a b\n  ^ group flat: fits, 75 columns left",
+            "\n",
+            0
+        );
+    }
+}
@@ -0,0 +1,254 @@
+//! Macros for building documents without long chains of `append` calls.
+
+use crate::{BuildDoc, DocAllocator, DocBuilder};
+
+/// Concatenates a number of documents (or values which can be converted into documents, like
+/// `&str`).
+///
+/// ```
+/// use pretty::{docs, Arena, DocAllocator};
+///
+/// let arena = Arena::<()>::new();
+/// let args = arena.intersperse(["x", "y"].iter().cloned(), arena.text(",").append(arena.line()));
+/// let doc = docs![
+///     &arena,
+///     "fn ",
+///     "f",
+///     "(",
+///     docs![&arena, arena.line_(), args].nest(4),
+///     arena.line_(),
+///     ")",
+/// ]
+/// .group();
+/// assert_eq!(doc.1.pretty(80).to_string(), "fn f(x, y)");
+/// assert_eq!(doc.1.pretty(5).to_string(), "fn f(\n    x,\n    y\n)");
+/// ```
+#[macro_export]
+macro_rules! docs {
+    ($alloc: expr $(, $doc: expr)* $(,)?) => {{
+        let alloc = $alloc;
+        $crate::DocAllocator::nil(alloc)
+            $(.append($doc))*
+    }};
+}
+
+/// Builds a document from a format string, similar to `format!`.
+///
+/// See [`format_doc`](fn.format_doc.html) for the syntax of the format string.
+///
+/// ```
+/// use pretty::{pretty_format, Arena, DocAllocator};
+///
+/// let arena = Arena::<()>::new();
+/// let body = arena.text("return").append(arena.line()).append("x");
+/// let doc = pretty_format!(&arena, "fn {}() {{{group}{nest 4}{line}{}{end}{line}{end}}}", "f", body);
+/// assert_eq!(doc.1.pretty(80).to_string(), "fn f() { return x }");
+/// assert_eq!(doc.1.pretty(10).to_string(), "fn f() {\n    return\n    x\n}");
+/// ```
+#[macro_export]
+macro_rules! pretty_format {
+    ($alloc: expr, $fmt: expr $(, $arg: expr)* $(,)?) => {
+        $crate::format_doc($alloc, $fmt, vec![$($crate::BuildDoc::from($arg)),*])
+    };
+}
+
+enum Frame {
+    Group,
+    Nest(isize),
+}
+
+/// Builds a document from the format string `fmt`, replacing placeholders with `args` and layout
+/// directives.
+///
+/// * `{}` is replaced by the next document in `args`
+/// * `{{` and `}}` are replaced by `{` and `}`
+/// * A newline, `{hardline}`, `{line}`, `{line_}`, `{softline}` and `{softline_}` insert the
+///   corresponding line break
+/// * `{group}` and `{nest N}` start a group or a nesting of `N` columns (`N` may be negative) which
+///   lasts until the matching `{end}`
+///
+/// # Panics
+///
+/// Panics if the format string is malformed, if a group or nesting is not closed or if the number
+/// of `{}` placeholders does not match the number of arguments.
+pub fn format_doc<'a, D, A>(
+    allocator: &'a D,
+    fmt: &'a str,
+    args: Vec<BuildDoc<'a, D::Doc, A>>,
+) -> DocBuilder<'a, D, A>
+where
+    D: ?Sized + DocAllocator<'a, A>,
+    A: 'a,
+{
+    let mut args = args.into_iter();
+    let mut stack = Vec::new();
+    let mut doc = allocator.nil();
+    let mut rest = fmt;
+
+    while let Some(i) = rest.find(['{', '}', '\n']) {
+        if i != 0 {
+            doc = doc.append(&rest[..i]);
+        }
+        let special = &rest[i..];
+        if let Some(after) = special.strip_prefix('\n') {
+            doc = doc.append(allocator.hardline());
+            rest = after;
+        } else if special.starts_with("{{") || special.starts_with("}}") {
+            doc = doc.append(&special[..1]);
+            rest = &special[2..];
+        } else if special.starts_with('}') {
+            panic!("Unmatched `}}` in format string: {:?}", fmt);
+        } else {
+            let end = special
+                .find('}')
+                .unwrap_or_else(|| panic!("Unclosed `{{` in format string: {:?}", fmt));
+            let directive = &special[1..end];
+            rest = &special[end + 1..];
+
+            doc = match directive {
+                "" => {
+                    let arg = args.next().unwrap_or_else(|| {
+                        panic!("Missing argument for `{{}}` in format string: {:?}", fmt)
+                    });
+                    doc.append(arg)
+                }
+                "hardline" => doc.append(allocator.hardline()),
+                "line" => doc.append(allocator.line()),
+                "line_" => doc.append(allocator.line_()),
+                "softline" => doc.append(allocator.softline()),
+                "softline_" => doc.append(allocator.softline_()),
+                "group" => {
+                    stack.push((Frame::Group, doc));
+                    allocator.nil()
+                }
+                "end" => {
+                    let (frame, outer) = stack.pop().unwrap_or_else(|| {
+                        panic!("Unmatched `{{end}}` in format string: {:?}", fmt)
+                    });
+                    outer.append(match frame {
+                        Frame::Group => doc.group(),
+                        Frame::Nest(offset) => doc.nest(offset),
+                    })
+                }
+                _ => {
+                    let offset = directive
+                        .strip_prefix("nest ")
+                        .and_then(|offset| offset.trim().parse().ok())
+                        .unwrap_or_else(|| {
+                            panic!(
+                                "Unknown directive `{{{}}}` in format string: {:?}",
+                                directive, fmt
+                            )
+                        });
+                    stack.push((Frame::Nest(offset), doc));
+                    allocator.nil()
+                }
+            };
+        }
+    }
+    if !rest.is_empty() {
+        doc = doc.append(rest);
+    }
+
+    assert!(
+        stack.is_empty(),
+        "Missing `{{end}}` in format string: {:?}",
+        fmt
+    );
+    assert!(
+        args.next().is_none(),
+        "Too many arguments for format string: {:?}",
+        fmt
+    );
+    doc
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Arena, DocAllocator, RcDoc};
+
+    macro_rules! test {
+        ($size:expr, $actual:expr, $expected:expr) => {
+            let mut s = String::new();
+            $actual.render_fmt($size, &mut s).unwrap();
+            difference::assert_diff!(&s, $expected, "\n", 0);
+        };
+    }
+
+    #[test]
+    fn docs_macro() {
+        let arena = Arena::<()>::new();
+        test!(80, docs![&arena].1, "");
+        test!(
+            80,
+            docs![&arena, "a", arena.text("b"), "c".to_string(),].1,
+            "abc"
+        );
+
+        let doc = docs![&arena, "let x =", docs![&arena, arena.line(), "1"].nest(4),].group();
+        test!(80, doc.1, "let x = 1");
+        test!(8, doc.1, "let x =\n    1");
+    }
+
+    #[test]
+    fn docs_macro_static_allocator() {
+        let doc: RcDoc<()> = docs![&crate::RcAllocator, "a", RcDoc::line(), "b"]
+            .group()
+            .into_doc();
+        test!(80, doc, "a b");
+    }
+
+    #[test]
+    fn format_text_and_escapes() {
+        let arena = Arena::<()>::new();
+        test!(80, pretty_format!(&arena, "").1, "");
+        test!(80, pretty_format!(&arena, "{{x}}").1, "{x}");
+        test!(80, pretty_format!(&arena, "a\nb").1, "a\nb");
+        test!(
+            80,
+            pretty_format!(&arena, "{} + {}", "1", arena.text("2")).1,
+            "1 + 2"
+        );
+    }
+
+    #[test]
+    fn format_directives() {
+        let arena = Arena::<()>::new();
+        let doc = pretty_format!(
+            &arena,
+            "[{group}{nest 2}{line_}{},{line}{}{end}{line_}{end}]",
+            "a",
+            "b"
+        );
+        test!(80, doc.1, "[a, b]");
+        test!(4, doc.1, "[\n  a,\n  b\n]");
+
+        let doc = pretty_format!(&arena, "a{softline}b{softline_}c");
+        test!(80, doc.1, "a bc");
+        test!(1, doc.1, "a\nb\nc");
+
+        let doc = pretty_format!(&arena, "{nest 2}a{hardline}b{end}");
+        test!(80, doc.1, "a\n  b");
+    }
+
+    #[test]
+    #[should_panic(expected = "Missing `{end}`")]
+    fn format_unclosed_group() {
+        let arena = Arena::<()>::new();
+        pretty_format!(&arena, "{group}a");
+    }
+
+    #[test]
+    #[should_panic(expected = "Too many arguments")]
+    fn format_too_many_arguments() {
+        let arena = Arena::<()>::new();
+        pretty_format!(&arena, "{}", "a", "b");
+    }
+
+    #[test]
+    #[should_panic(expected = "Unknown directive `{nest x}`")]
+    fn format_unknown_directive() {
+        let arena = Arena::<()>::new();
+        pretty_format!(&arena, "{nest x}");
+    }
+}
@@ -0,0 +1,432 @@
+//! `Pretty` implementations for types in the standard library.
+//!
+//! Collections and other compound values are laid out like `{:#?}` would format them when they do
+//! not fit on the current line, but are kept on a single line whenever they fit.
+
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet, LinkedList, VecDeque},
+    rc::Rc,
+    sync::Arc,
+};
+
+use crate::{DocAllocator, DocBuilder, Pretty, TrailingComma};
+
+/// Lays out `docs` between `open` and `close`, separated by commas. If the documents do not fit on
+/// a single line each one is put on its own line, followed by a trailing comma.
+fn delimited<'a, D, A, I>(
+    allocator: &'a D,
+    open: &'static str,
+    docs: I,
+    close: &'static str,
+) -> DocBuilder<'a, D, A>
+where
+    D: ?Sized + DocAllocator<'a, A>,
+    A: 'a,
+    I: IntoIterator<Item = DocBuilder<'a, D, A>>,
+{
+    allocator
+        .delimited(open, ",", close, TrailingComma::IfBroken)
+        .build(docs)
+}
+
+macro_rules! impl_display {
+    ($($ty: ty),*) => {
+        $(
+            impl<'a, D, A> Pretty<'a, D, A> for $ty
+            where
+                A: 'a,
+                D: ?Sized + DocAllocator<'a, A>,
+            {
+                fn pretty(self, allocator: &'a D) -> DocBuilder<'a, D, A> {
+                    allocator.as_string(self)
+                }
+            }
+
+            impl<'a, D, A> Pretty<'a, D, A> for &'a $ty
+            where
+                A: 'a,
+                D: ?Sized + DocAllocator<'a, A>,
+            {
+                fn pretty(self, allocator: &'a D) -> DocBuilder<'a, D, A> {
+                    allocator.as_string(self)
+                }
+            }
+        )*
+    };
+}
+
+impl_display!(bool, i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+
+/// Types whose `Display` output is ambiguous or loses information, such as strings which may
+/// contain separators, are laid out with `Debug` instead
+macro_rules! impl_debug {
+    ($($ty: ty),*) => {
+        $(
+            impl<'a, D, A> Pretty<'a, D, A> for $ty
+            where
+                A: 'a,
+                D: ?Sized + DocAllocator<'a, A>,
+            {
+                fn pretty(self, allocator: &'a D) -> DocBuilder<'a, D, A> {
+                    allocator.text(format!("{:?}", self))
+                }
+            }
+
+            impl<'a, D, A> Pretty<'a, D, A> for &'a $ty
+            where
+                A: 'a,
+                D: ?Sized + DocAllocator<'a, A>,
+            {
+                fn pretty(self, allocator: &'a D) -> DocBuilder<'a, D, A> {
+                    allocator.text(format!("{:?}", self))
+                }
+            }
+        )*
+    };
+}
+
+impl_debug!(char, f32, f64, String);
+
+impl<'a, D, A> Pretty<'a, D, A> for &'a str
+where
+    A: 'a,
+    D: ?Sized + DocAllocator<'a, A>,
+{
+    fn pretty(self, allocator: &'a D) -> DocBuilder<'a, D, A> {
+        allocator.text(format!("{:?}", self))
+    }
+}
+
+impl<'a, D, A> Pretty<'a, D, A> for &'a Cow<'_, str>
+where
+    A: 'a,
+    D: ?Sized + DocAllocator<'a, A>,
+{
+    fn pretty(self, allocator: &'a D) -> DocBuilder<'a, D, A> {
+        allocator.text(format!("{:?}", self))
+    }
+}
+
+macro_rules! impl_deref {
+    ($($ty: ident),*) => {
+        $(
+            impl<'a, D, A, T> Pretty<'a, D, A> for &'a $ty<T>
+            where
+                A: 'a,
+                D: ?Sized + DocAllocator<'a, A>,
+                T: ?Sized,
+                &'a T: Pretty<'a, D, A>,
+            {
+                fn pretty(self, allocator: &'a D) -> DocBuilder<'a, D, A> {
+                    (&**self).pretty(allocator)
+                }
+            }
+        )*
+    };
+}
+
+impl_deref!(Box, Rc, Arc);
+
+impl<'a, D, A, T> Pretty<'a, D, A> for &'a &T
+where
+    A: 'a,
+    D: ?Sized + DocAllocator<'a, A>,
+    T: ?Sized,
+    &'a T: Pretty<'a, D, A>,
+{
+    fn pretty(self, allocator: &'a D) -> DocBuilder<'a, D, A> {
+        (&**self).pretty(allocator)
+    }
+}
+
+impl<'a, D, A, T> Pretty<'a, D, A> for &'a Option<T>
+where
+    A: 'a,
+    D: ?Sized + DocAllocator<'a, A>,
+    &'a T: Pretty<'a, D, A>,
+{
+    fn pretty(self, allocator: &'a D) -> DocBuilder<'a, D, A> {
+        match self {
+            Some(x) => allocator.text("Some").append(delimited(
+                allocator,
+                "(",
+                Some(x.pretty(allocator)),
+                ")",
+            )),
+            None => allocator.text("None"),
+        }
+    }
+}
+
+impl<'a, D, A, T, E> Pretty<'a, D, A> for &'a Result<T, E>
+where
+    A: 'a,
+    D: ?Sized + DocAllocator<'a, A>,
+    &'a T: Pretty<'a, D, A>,
+    &'a E: Pretty<'a, D, A>,
+{
+    fn pretty(self, allocator: &'a D) -> DocBuilder<'a, D, A> {
+        match self {
+            Ok(x) => allocator.text("Ok").append(delimited(
+                allocator,
+                "(",
+                Some(x.pretty(allocator)),
+                ")",
+            )),
+            Err(x) => allocator.text("Err").append(delimited(
+                allocator,
+                "(",
+                Some(x.pretty(allocator)),
+                ")",
+            )),
+        }
+    }
+}
+
+macro_rules! impl_seq {
+    ($open: expr, $close: expr, $ty: ident $(<$($param: ident),*>)?) => {
+        impl<'a, D, A, T $($(, $param)*)?> Pretty<'a, D, A> for &'a $ty<T $($(, $param)*)?>
+        where
+            A: 'a,
+            D: ?Sized + DocAllocator<'a, A>,
+            &'a T: Pretty<'a, D, A>,
+        {
+            fn pretty(self, allocator: &'a D) -> DocBuilder<'a, D, A> {
+                delimited(
+                    allocator,
+                    $open,
+                    self.iter().map(|x| x.pretty(allocator)),
+                    $close,
+                )
+            }
+        }
+    };
+}
+
+impl_seq!("[", "]", Vec);
+impl_seq!("[", "]", VecDeque);
+impl_seq!("[", "]", LinkedList);
+impl_seq!("[", "]", BinaryHeap);
+impl_seq!("{", "}", BTreeSet);
+impl_seq!("{", "}", HashSet<S>);
+
+impl<'a, D, A, T> Pretty<'a, D, A> for &'a [T]
+where
+    A: 'a,
+    D: ?Sized + DocAllocator<'a, A>,
+    &'a T: Pretty<'a, D, A>,
+{
+    fn pretty(self, allocator: &'a D) -> DocBuilder<'a, D, A> {
+        delimited(
+            allocator,
+            "[",
+            self.iter().map(|x| x.pretty(allocator)),
+            "]",
+        )
+    }
+}
+
+impl<'a, D, A, T, const N: usize> Pretty<'a, D, A> for &'a [T; N]
+where
+    A: 'a,
+    D: ?Sized + DocAllocator<'a, A>,
+    &'a T: Pretty<'a, D, A>,
+{
+    fn pretty(self, allocator: &'a D) -> DocBuilder<'a, D, A> {
+        self[..].pretty(allocator)
+    }
+}
+
+macro_rules! impl_map {
+    ($ty: ident $(<$($param: ident),*>)?) => {
+        impl<'a, D, A, K, V $($(, $param)*)?> Pretty<'a, D, A> for &'a $ty<K, V $($(, $param)*)?>
+        where
+            A: 'a,
+            D: ?Sized + DocAllocator<'a, A>,
+            &'a K: Pretty<'a, D, A>,
+            &'a V: Pretty<'a, D, A>,
+        {
+            fn pretty(self, allocator: &'a D) -> DocBuilder<'a, D, A> {
+                delimited(
+                    allocator,
+                    "{",
+                    self.iter().map(|(key, value)| {
+                        key.pretty(allocator)
+                            .append(": ")
+                            .append(value.pretty(allocator))
+                    }),
+                    "}",
+                )
+            }
+        }
+    };
+}
+
+impl_map!(BTreeMap);
+impl_map!(HashMap<S>);
+
+impl<'a, D, A> Pretty<'a, D, A> for &'a ()
+where
+    A: 'a,
+    D: ?Sized + DocAllocator<'a, A>,
+{
+    fn pretty(self, allocator: &'a D) -> DocBuilder<'a, D, A> {
+        allocator.text("()")
+    }
+}
+
+impl<'a, D, A, T> Pretty<'a, D, A> for &'a (T,)
+where
+    A: 'a,
+    D: ?Sized + DocAllocator<'a, A>,
+    &'a T: Pretty<'a, D, A>,
+{
+    fn pretty(self, allocator: &'a D) -> DocBuilder<'a, D, A> {
+        // A tuple with a single element always needs its trailing comma
+        allocator
+            .delimited("(", ",", ")", TrailingComma::Always)
+            .build(Some(self.0.pretty(allocator)))
+    }
+}
+
+macro_rules! impl_tuple {
+    ($($name: ident)+) => {
+        impl<'a, D, A, $($name),+> Pretty<'a, D, A> for &'a ($($name,)+)
+        where
+            A: 'a,
+            D: ?Sized + DocAllocator<'a, A>,
+            $(&'a $name: Pretty<'a, D, A>,)+
+        {
+            #[allow(non_snake_case)]
+            fn pretty(self, allocator: &'a D) -> DocBuilder<'a, D, A> {
+                let ($($name,)+) = self;
+                delimited(allocator, "(", vec![$($name.pretty(allocator)),+], ")")
+            }
+        }
+    };
+}
+
+impl_tuple!(T0 T1);
+impl_tuple!(T0 T1 T2);
+impl_tuple!(T0 T1 T2 T3);
+impl_tuple!(T0 T1 T2 T3 T4);
+impl_tuple!(T0 T1 T2 T3 T4 T5);
+impl_tuple!(T0 T1 T2 T3 T4 T5 T6);
+impl_tuple!(T0 T1 T2 T3 T4 T5 T6 T7);
+impl_tuple!(T0 T1 T2 T3 T4 T5 T6 T7 T8);
+impl_tuple!(T0 T1 T2 T3 T4 T5 T6 T7 T8 T9);
+impl_tuple!(T0 T1 T2 T3 T4 T5 T6 T7 T8 T9 T10);
+impl_tuple!(T0 T1 T2 T3 T4 T5 T6 T7 T8 T9 T10 T11);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Arena;
+
+    macro_rules! test {
+        ($size:expr, $actual:expr, $expected:expr) => {
+            let actual = &$actual;
+            let arena = Arena::<()>::new();
+            let doc = actual.pretty(&arena);
+            difference::assert_diff!(&doc.1.pretty($size).to_string(), $expected, "\n", 0);
+        };
+        ($actual:expr, $expected:expr) => {
+            test!(70, $actual, $expected)
+        };
+    }
+
+    #[test]
+    fn primitives() {
+        test!(1, "1");
+        test!(-2.5, "-2.5");
+        test!(true, "true");
+        test!('c', "'c'");
+        test!("text", "\"text\"");
+        test!(String::from("text"), "\"text\"");
+        test!((), "()");
+    }
+
+    #[test]
+    fn sequences() {
+        test!(Vec::<u32>::new(), "[]");
+        test!(vec![1, 2, 3], "[1, 2, 3]");
+        test!(6, vec![1, 2, 3], "[\n    1,\n    2,\n    3,\n]");
+        test!([1, 2], "[1, 2]");
+        test!(&[1, 2][..], "[1, 2]");
+        test!(VecDeque::from(vec![1]), "[1]");
+        test!(
+            vec!["a", "b"].into_iter().collect::<BTreeSet<_>>(),
+            "{\"a\", \"b\"}"
+        );
+        test!(vec!["a, b", "c"], "[\"a, b\", \"c\"]");
+    }
+
+    #[test]
+    fn nested_sequences_break_outermost_first() {
+        let nested = vec![vec![1, 2], vec![3, 4, 5]];
+        test!(nested, "[[1, 2], [3, 4, 5]]");
+        test!(16, nested, "[\n    [1, 2],\n    [3, 4, 5],\n]");
+        test!(
+            8,
+            nested,
+            "[\n    [\n        1,\n        2,\n    ],\n    [\n        3,\n        4,\n        5,\n    ],\n]"
+        );
+    }
+
+    #[test]
+    fn maps() {
+        let map = vec![(1, "one"), (2, "two")]
+            .into_iter()
+            .collect::<BTreeMap<_, _>>();
+        test!(map, "{1: \"one\", 2: \"two\"}");
+        test!(10, map, "{\n    1: \"one\",\n    2: \"two\",\n}");
+        let map = vec![("key", vec![1])]
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+        test!(map, "{\"key\": [1]}");
+    }
+
+    #[test]
+    fn options_and_results() {
+        test!(Some(1), "Some(1)");
+        test!(None::<u32>, "None");
+        test!(Ok::<_, ()>(vec![1]), "Ok([1])");
+        test!(Err::<(), _>("failed"), "Err(\"failed\")");
+        test!(11, Some(vec![1, 2]), "Some(\n    [1, 2],\n)");
+    }
+
+    #[test]
+    fn tuples() {
+        test!((1,), "(1,)");
+        test!(3, (1,), "(\n    1,\n)");
+        test!((1, "a", Some(2)), "(1, \"a\", Some(2))");
+        test!(5, (1, "a"), "(\n    1,\n    \"a\",\n)");
+    }
+
+    #[test]
+    fn pointers() {
+        test!(Box::new(1), "1");
+        test!(Rc::new(vec![1]), "[1]");
+        test!(Arc::new("a"), "\"a\"");
+        test!(Cow::Borrowed("a"), "\"a\"");
+    }
+
+    #[test]
+    fn leaves_match_debug() {
+        macro_rules! debug {
+            ($($actual:expr),*) => {
+                $(test!($actual, &format!("{:?}", $actual));)*
+            };
+        }
+        debug!(
+            "a, b",
+            "quote \" and \\ and \n",
+            String::from("tab\t"),
+            Cow::Borrowed("é")
+        );
+        debug!('c', '\'', '\n');
+        debug!(1.0f64, -0.5f32, f64::NAN, f64::INFINITY, 1e21f64);
+        debug!(vec!["a, b", "c"], Some('x'), (1.0f64, "s"));
+    }
+}
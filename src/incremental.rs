@@ -0,0 +1,492 @@
+//! Incremental rendering which reuses the layout of groups that are unchanged since the previous
+//! render.
+//!
+//! The layout of a group is cached by the address of the group together with the column and
+//! indentation it starts at. A group may still be laid out differently if the text following it
+//! changed, so every check which looked past the end of the group while laying it out is stored
+//! with the entry and repeated before the entry is reused.
+
+use std::{collections::HashMap, marker::PhantomData};
+
+use crate::{
+    render::{Best, CachedGroup, Event, GroupCache, GroupKey, Output},
+    RefDoc, RenderAnnotated, RenderOptions,
+};
+
+struct Entry<A> {
+    layout: CachedGroup<A>,
+    /// The groups inside this group which also have entries
+    children: Vec<GroupKey>,
+    /// The last render which used this entry
+    generation: usize,
+}
+
+/// Caches the layout of the groups of an arena allocated document between calls to
+/// `RefDoc::render_incremental`.
+///
+/// Groups are identified by their address, so only documents allocated in the same arena should
+/// be rendered with a cache. Entries which were not used by the latest render are discarded, as
+/// are all entries if the width changes.
+pub struct LayoutCache<'a, A = ()> {
+    width: usize,
+    generation: usize,
+    entries: HashMap<GroupKey, Entry<A>>,
+    /// The arena must outlive the cache so that the addresses of the groups are not reused
+    _marker: PhantomData<RefDoc<'a, A>>,
+}
+
+impl<A> Default for LayoutCache<'_, A> {
+    fn default() -> Self {
+        LayoutCache {
+            width: 0,
+            generation: 0,
+            entries: HashMap::new(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<A> LayoutCache<'_, A> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of cached groups
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// A group whose output is being recorded
+struct Recording {
+    key: GroupKey,
+    /// The size of the command stack and the number of open annotations when the group started,
+    /// once they are back to these the group has been laid out
+    base: usize,
+    annotations: usize,
+    log_start: usize,
+    lookaheads: Vec<(usize, usize, bool)>,
+    children: Vec<GroupKey>,
+}
+
+/// Records the layout of the groups of a single render into a `LayoutCache`
+struct Recorder<'c, 'a, A> {
+    cache: &'c mut LayoutCache<'a, A>,
+    recordings: Vec<Recording>,
+    /// The output since the start of the outermost recording
+    log: Vec<Event<String, A>>,
+}
+
+impl<A> GroupCache<A> for Recorder<'_, '_, A>
+where
+    A: Clone,
+{
+    fn get(&self, key: GroupKey) -> Option<&CachedGroup<A>> {
+        self.cache.entries.get(&key).map(|entry| &entry.layout)
+    }
+
+    fn reuse(&mut self, key: GroupKey) {
+        mark_used(&mut self.cache.entries, key, self.cache.generation);
+        if let Some(parent) = self.recordings.last_mut() {
+            parent.children.push(key);
+            let events = &self.cache.entries[&key].layout.events;
+            self.log.extend(events.iter().cloned());
+        }
+    }
+
+    fn start(&mut self, key: GroupKey, base: usize, annotations: usize) {
+        self.recordings.push(Recording {
+            key,
+            base,
+            annotations,
+            log_start: self.log.len(),
+            lookaheads: Vec::new(),
+            children: Vec::new(),
+        });
+    }
+
+    /// Records the checks which looked past the end of a recorded group
+    fn check(&mut self, trace: &[(usize, usize)], ind: usize, fits: bool) {
+        for recording in &mut self.recordings {
+            if let Some(&(_, pos)) = trace.iter().find(|&&(i, _)| i < recording.base) {
+                let lookahead = (pos, ind, fits);
+                if !recording.lookaheads.contains(&lookahead) {
+                    recording.lookaheads.push(lookahead);
+                }
+            }
+        }
+    }
+
+    fn record(&mut self, event: Event<&str, &A>) {
+        let log_start = match self.recordings.last() {
+            Some(recording) => recording.log_start,
+            None => return,
+        };
+        // Text logged before the innermost recording started is not part of its output, so it
+        // can not be extended
+        let last = if self.log.len() > log_start {
+            self.log.last_mut()
+        } else {
+            None
+        };
+        match (event, last) {
+            (Event::Text(s), Some(Event::Text(text))) => text.push_str(s),
+            (Event::Text(s), _) => self.log.push(Event::Text(s.to_string())),
+            (Event::Line(ind), _) => self.log.push(Event::Line(ind)),
+            (Event::PushAnnotation(ann), _) => self.log.push(Event::PushAnnotation(ann.clone())),
+            (Event::PopAnnotation, _) => self.log.push(Event::PopAnnotation),
+        }
+    }
+
+    /// Stores the groups which have been completely laid out
+    fn end(&mut self, base: usize, annotations: usize) {
+        while self
+            .recordings
+            .last()
+            .is_some_and(|recording| recording.base == base && recording.annotations == annotations)
+        {
+            let recording = self.recordings.pop().unwrap();
+            self.cache.entries.insert(
+                recording.key,
+                Entry {
+                    layout: CachedGroup {
+                        events: self.log[recording.log_start..].to_vec(),
+                        lookaheads: recording.lookaheads,
+                    },
+                    children: recording.children,
+                    generation: self.cache.generation,
+                },
+            );
+            match self.recordings.last_mut() {
+                Some(parent) => parent.children.push(recording.key),
+                None => self.log.clear(),
+            }
+        }
+    }
+}
+
+pub(crate) fn render<'a, A, W>(
+    root: RefDoc<'a, A>,
+    width: usize,
+    cache: &mut LayoutCache<'a, A>,
+    out: &mut W,
+) -> Result<(), W::Error>
+where
+    A: Clone,
+    W: ?Sized + RenderAnnotated<A>,
+{
+    if cache.width != width {
+        cache.entries.clear();
+        cache.width = width;
+    }
+    cache.generation += 1;
+
+    let mut recorder = Recorder {
+        cache,
+        recordings: Vec::new(),
+        log: Vec::new(),
+    };
+    {
+        let temp_arena = typed_arena::Arena::new();
+        let mut out = Output::new(out, &RenderOptions::new(width));
+        let mut best = Best::new(&temp_arena, root.0, width).cache(&mut recorder);
+        best.run(&mut out, |_| false)?;
+        best.finish(&mut out)?;
+    }
+
+    let generation = recorder.cache.generation;
+    recorder
+        .cache
+        .entries
+        .retain(|_, entry| entry.generation == generation);
+
+    Ok(())
+}
+
+/// Marks the entry at `key` and the entries of the groups inside it as used by the current render
+fn mark_used<A>(entries: &mut HashMap<GroupKey, Entry<A>>, key: GroupKey, generation: usize) {
+    let mut stack = vec![key];
+    while let Some(key) = stack.pop() {
+        if let Some(entry) = entries.get_mut(&key) {
+            entry.generation = generation;
+            stack.extend(entry.children.iter().cloned());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, fmt};
+
+    use proptest::prelude::*;
+
+    use crate::{Arena, DocAllocator, FmtWrite, LayoutCache, RefDoc, Render, RenderAnnotated};
+
+    fn program<'a>(arena: &'a Arena<'a, ()>, items: &[RefDoc<'a, ()>]) -> RefDoc<'a, ()> {
+        arena
+            .intersperse(items.iter().cloned(), arena.hardline())
+            .into_doc()
+    }
+
+    fn render<'a>(doc: RefDoc<'a, ()>, width: usize, cache: &mut LayoutCache<'a, ()>) -> String {
+        let mut s = String::new();
+        doc.render_incremental(width, cache, &mut FmtWrite::new(&mut s))
+            .unwrap();
+        s
+    }
+
+    macro_rules! test {
+        ($size:expr, $cache:expr, $doc:expr) => {{
+            let doc = $doc;
+            let s = render(doc, $size, $cache);
+            difference::assert_diff!(&s, &doc.pretty($size).to_string(), "\n", 0);
+            s
+        }};
+    }
+
+    #[test]
+    fn same_output_as_best() {
+        let arena = Arena::<()>::new();
+        let item = |name: &str, args: &[&str]| {
+            arena
+                .text(format!("fn {}", name))
+                .append(arena.tupled(args.iter().map(|arg| arg.to_string())).group())
+                .append(";")
+                .group()
+                .into_doc()
+        };
+
+        let mut items = vec![
+            item("first", &["a", "b"]),
+            item("second", &["long_argument", "another_one"]),
+            item("third", &[]),
+        ];
+        let mut cache = LayoutCache::new();
+        for &width in &[80, 20, 20, 10, 80] {
+            test!(width, &mut cache, program(&arena, &items));
+        }
+
+        items[1] = item("second", &["x"]);
+        items.push(item("fourth", &["c", "d", "e"]));
+        for &width in &[80, 20, 10, 10] {
+            test!(width, &mut cache, program(&arena, &items));
+        }
+    }
+
+    #[test]
+    fn lookahead_past_group_is_checked() {
+        let arena = Arena::<()>::new();
+        // Whether the group fits depends on the text after it
+        let group = arena.text("a").append(arena.line()).append("b").group();
+        let mut cache = LayoutCache::new();
+
+        let doc = arena.nil().append(group.clone()).into_doc();
+        assert_eq!(test!(4, &mut cache, doc), "a b");
+        let doc = arena.nil().append(group.clone()).append("cd").into_doc();
+        assert_eq!(test!(4, &mut cache, doc), "a\nbcd");
+        assert_eq!(test!(4, &mut cache, doc), "a\nbcd");
+        let doc = arena.nil().append(group).append("c").into_doc();
+        assert_eq!(test!(4, &mut cache, doc), "a bc");
+    }
+
+    #[test]
+    fn unchanged_groups_are_reused() {
+        let calls = Cell::new(0);
+        let arena = Arena::<()>::new();
+        let item = |name: &'static str| {
+            arena
+                .text(name)
+                .append(arena.column(|_| {
+                    calls.set(calls.get() + 1);
+                    arena.nil().into_doc()
+                }))
+                .append(arena.line())
+                .append("body")
+                .group()
+                .into_doc()
+        };
+
+        let mut items = vec![item("a"), item("b"), item("c")];
+        let mut cache = LayoutCache::new();
+        test!(80, &mut cache, program(&arena, &items));
+        assert_eq!(cache.len(), 3);
+
+        // Nothing is laid out again
+        calls.set(0);
+        assert_eq!(
+            render(program(&arena, &items), 80, &mut cache),
+            "a body\nb body\nc body"
+        );
+        assert_eq!(calls.get(), 0);
+
+        // Only the replaced item is laid out again (the column is checked while fitting and again
+        // when printing)
+        items[1] = item("B");
+        calls.set(0);
+        assert_eq!(
+            render(program(&arena, &items), 80, &mut cache),
+            "a body\nB body\nc body"
+        );
+        assert_eq!(calls.get(), 2);
+        assert_eq!(cache.len(), 3);
+
+        // Nothing can be reused at another width
+        calls.set(0);
+        render(program(&arena, &items), 40, &mut cache);
+        assert_eq!(calls.get(), 6);
+    }
+
+    /// Writes the annotations as brackets
+    struct Brackets(String);
+
+    impl Render for Brackets {
+        type Error = fmt::Error;
+
+        fn write_str(&mut self, s: &str) -> Result<usize, fmt::Error> {
+            self.0.push_str(s);
+            Ok(s.len())
+        }
+
+        fn write_zero_error(&self) -> fmt::Error {
+            fmt::Error
+        }
+    }
+
+    impl RenderAnnotated<usize> for Brackets {
+        fn push_annotation(&mut self, ann: &usize) -> Result<(), fmt::Error> {
+            self.0.push_str(&format!("[{}", ann));
+            Ok(())
+        }
+
+        fn pop_annotation(&mut self) -> Result<(), fmt::Error> {
+            self.0.push(']');
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn annotations_are_replayed() {
+        let arena = Arena::<usize>::new();
+        // The annotation and the group end at the same command, in both orders
+        let item = |name: &'static str| {
+            let inner = arena.text(name).append(arena.line()).append("x");
+            arena
+                .nil()
+                .append(inner.clone().annotate(1).group())
+                .append(inner.group().annotate(2))
+                .into_doc()
+        };
+        let items = [item("a"), item("b")];
+        let doc = arena
+            .intersperse(items.iter().cloned(), arena.hardline())
+            .into_doc();
+
+        let mut expected = Brackets(String::new());
+        doc.render_raw(80, &mut expected).unwrap();
+        assert_eq!(expected.0, "[1a x][2a x]\n[1b x][2b x]");
+
+        let mut cache = LayoutCache::new();
+        for _ in 0..2 {
+            let mut out = Brackets(String::new());
+            doc.render_incremental(80, &mut cache, &mut out).unwrap();
+            assert_eq!(out.0, expected.0);
+        }
+        assert_eq!(cache.len(), 4);
+    }
+
+    #[test]
+    fn shared_group_after_text() {
+        let arena = Arena::<()>::new();
+        let group = arena.text("hello").group().into_doc();
+        let doc = arena
+            .concat(vec![
+                arena.text("ab"),
+                arena.nil().append(group),
+                arena.hardline(),
+                arena.text("ab"),
+                arena.nil().append(group).group(),
+            ])
+            .group()
+            .into_doc();
+        let mut cache = LayoutCache::new();
+        assert_eq!(test!(5, &mut cache, doc), "abhello\nabhello");
+        assert_eq!(test!(5, &mut cache, doc), "abhello\nabhello");
+    }
+
+    /// The structure of a generated document, where `Shared` refers to one of a few documents
+    /// which are used in several places
+    #[derive(Clone, Debug)]
+    enum Shape {
+        Text(&'static str),
+        Line,
+        HardLine,
+        Concat(Vec<Shape>),
+        Group(Box<Shape>),
+        Nest(isize, Box<Shape>),
+        Shared(usize),
+    }
+
+    fn shape(shared: bool) -> impl Strategy<Value = Shape> {
+        let mut leaves = vec![
+            prop::sample::select(vec!["", "a", "ab", "hello"])
+                .prop_map(Shape::Text)
+                .boxed(),
+            Just(Shape::Line).boxed(),
+            Just(Shape::HardLine).boxed(),
+        ];
+        if shared {
+            leaves.push((0usize..3).prop_map(Shape::Shared).boxed());
+        }
+        prop::strategy::Union::new(leaves).prop_recursive(4, 32, 4, |inner| {
+            prop_oneof![
+                prop::collection::vec(inner.clone(), 0..5).prop_map(Shape::Concat),
+                inner.clone().prop_map(|s| Shape::Group(Box::new(s))),
+                (0isize..3, inner).prop_map(|(i, s)| Shape::Nest(i, Box::new(s))),
+            ]
+        })
+    }
+
+    fn build<'a>(
+        arena: &'a Arena<'a, ()>,
+        shape: &Shape,
+        shared: &[RefDoc<'a, ()>],
+    ) -> RefDoc<'a, ()> {
+        let doc = match shape {
+            Shape::Text(s) => arena.text(*s),
+            Shape::Line => arena.line(),
+            Shape::HardLine => arena.hardline(),
+            Shape::Concat(shapes) => arena.concat(shapes.iter().map(|s| build(arena, s, shared))),
+            Shape::Group(s) => arena.nil().append(build(arena, s, shared)).group(),
+            Shape::Nest(i, s) => arena.nil().append(build(arena, s, shared)).nest(*i),
+            Shape::Shared(i) => return shared[*i],
+        };
+        doc.into_doc()
+    }
+
+    proptest! {
+        #[test]
+        fn shared_documents_same_output_as_best(
+            shared in prop::collection::vec(shape(false), 3),
+            shape in shape(true),
+        ) {
+            let arena = Arena::<()>::new();
+            let shared = shared.iter().map(|s| build(&arena, s, &[])).collect::<Vec<_>>();
+            let doc = arena.nil().append(build(&arena, &shape, &shared)).group().into_doc();
+            for width in [0, 3, 5, 10, 80] {
+                let mut cache = LayoutCache::new();
+                let expected = doc.pretty(width).to_string();
+                // The second render reuses the layouts stored by the first
+                prop_assert_eq!(render(doc, width, &mut cache), expected.clone());
+                prop_assert_eq!(render(doc, width, &mut cache), expected);
+            }
+        }
+    }
+}
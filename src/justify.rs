@@ -0,0 +1,121 @@
+//! Fully justified text.
+
+use std::{borrow::Cow, rc::Rc};
+
+use crate::{spaces, DocAllocator, DocBuilder};
+
+/// Splits `text` into words and lays them out so that every line but the last one ends exactly at
+/// the right margin. The lines are computed when rendering, once the starting column, indentation
+/// and page width are known.
+pub(crate) fn justify<'a, D, A>(allocator: &'a D, text: Cow<'a, str>) -> DocBuilder<'a, D, A>
+where
+    D: ?Sized + DocAllocator<'a, A>,
+    A: 'a,
+{
+    let words: Rc<[Cow<'a, str>]> = match text {
+        Cow::Borrowed(text) => text.split_whitespace().map(Cow::Borrowed).collect(),
+        Cow::Owned(text) => text
+            .split_whitespace()
+            .map(|word| Cow::Owned(word.to_string()))
+            .collect(),
+    };
+
+    allocator.nesting(move |indent| {
+        let words = words.clone();
+        allocator
+            .column(move |column| {
+                let words = words.clone();
+                allocator
+                    .page_width(move |page_width| {
+                        lines(allocator, &words, column, indent, page_width).into_doc()
+                    })
+                    .into_doc()
+            })
+            .into_doc()
+    })
+}
+
+/// Breaks `words` into lines greedily, padding the gaps of each line except the last
+fn lines<'a, D, A>(
+    allocator: &'a D,
+    words: &[Cow<'a, str>],
+    column: usize,
+    indent: usize,
+    page_width: usize,
+) -> DocBuilder<'a, D, A>
+where
+    D: ?Sized + DocAllocator<'a, A>,
+    A: 'a,
+{
+    let mut doc = allocator.nil();
+    let mut available = page_width.saturating_sub(column);
+    let mut start = 0;
+    while start < words.len() {
+        let mut end = start + 1;
+        let mut width = words[start].len();
+        while end < words.len() && width + 1 + words[end].len() <= available {
+            width += 1 + words[end].len();
+            end += 1;
+        }
+
+        let last = end == words.len();
+        let gaps = end - start - 1;
+        // A single word may be wider than the line, in which case there are no gaps to pad
+        let extra = if last || gaps == 0 {
+            0
+        } else {
+            available - width
+        };
+        for (i, word) in words[start..end].iter().enumerate() {
+            if i != 0 {
+                // Spread the remainder over the leftmost gaps
+                let padding = 1 + extra / gaps + usize::from(i <= extra % gaps);
+                doc = doc.append(spaces(allocator, padding));
+            }
+            doc = doc.append(allocator.text(word.clone()));
+        }
+        if !last {
+            doc = doc.append(allocator.hardline());
+        }
+
+        start = end;
+        available = page_width.saturating_sub(indent);
+    }
+    doc
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Arena, DocAllocator};
+
+    macro_rules! test {
+        ($size:expr, $actual:expr, $expected:expr) => {
+            let mut s = String::new();
+            $actual.render_fmt($size, &mut s).unwrap();
+            difference::assert_diff!(&s, $expected, "\n", 0);
+        };
+    }
+
+    #[test]
+    fn justify_lines() {
+        let arena = Arena::<()>::new();
+        let doc = arena.justify("a bb ccc dd e fff gggg h");
+        test!(80, doc.1, "a bb ccc dd e fff gggg h");
+        test!(10, doc.1, "a  bb  ccc\ndd  e  fff\ngggg h");
+        test!(11, doc.1, "a bb ccc dd\ne  fff gggg\nh");
+    }
+
+    #[test]
+    fn justify_with_indentation() {
+        let arena = Arena::<()>::new();
+        let doc = arena
+            .text("> ")
+            .append(arena.justify(String::from("one two three four")))
+            .nest(2);
+        test!(12, doc.1, "> one    two\n  three four");
+
+        // Words which are wider than the line are put on a line of their own
+        let doc = arena.justify("a verylongword b c");
+        test!(5, doc.1, "a\nverylongword\nb c");
+    }
+}
@@ -140,15 +140,56 @@
 #[cfg(feature = "termcolor")]
 pub extern crate termcolor;
 
-use std::{borrow::Cow, convert::TryInto, fmt, io, ops::Deref, rc::Rc};
+/// Derives `Pretty` for structs and enums, see the `pretty-derive` crate for details.
+#[cfg(feature = "derive")]
+pub use pretty_derive::Pretty;
+
+use std::{
+    borrow::Cow,
+    cell::{Cell, OnceCell},
+    convert::TryInto,
+    fmt,
+    hash::{Hash, Hasher},
+    io, mem,
+    ops::Deref,
+    rc::Rc,
+};
 #[cfg(feature = "termcolor")]
 use termcolor::{ColorSpec, WriteColor};
 
+mod debug;
+mod dot;
+mod dump;
+mod elide;
+mod explain;
+mod format;
+mod impls;
+mod incremental;
+mod justify;
 mod render;
+#[cfg(any(feature = "async", feature = "tokio"))]
+mod render_async;
+mod simplify;
+mod stream;
+mod table;
+#[cfg(feature = "unicode-linebreak")]
+mod wrap;
 
 #[cfg(feature = "termcolor")]
 pub use self::render::TermColored;
-pub use self::render::{FmtWrite, IoWrite, Render, RenderAnnotated};
+#[cfg(feature = "unicode-linebreak")]
+pub use self::wrap::Wrap;
+pub use self::{
+    debug::{debug, debug_doc},
+    format::format_doc,
+    incremental::LayoutCache,
+    render::{
+        Choice, Counting, Decision, DecisionKind, FmtWrite, IoWrite, MapText, Overflow, Render,
+        RenderAnnotated, RenderOptions, RenderStats, Tee,
+    },
+    stream::{render_stream, StreamPrinter, Token},
+    table::{Alignment, Table},
+};
 
 /// The concrete document type. This type is not meant to be used directly. Instead use the static
 /// functions on `Doc` or the methods on an `DocAllocator`.
@@ -170,6 +211,9 @@ pub enum Doc<'a, T: DocPtr<'a, A>, A = ()> {
     Union(T, T),
     Column(T::ColumnFn),
     Nesting(T::ColumnFn),
+    PageWidth(T::ColumnFn),
+    /// A document built by `DocAllocator::lazy`, the closure ignores its argument
+    Lazy(T::ColumnFn),
 }
 
 pub type SmallText = arrayvec::ArrayString<[u8; 22]>;
@@ -196,8 +240,139 @@ where
                 f.debug_tuple("Annotated").field(ann).field(doc).finish()
             }
             Doc::Union(ref l, ref r) => f.debug_tuple("Union").field(l).field(r).finish(),
-            Doc::Column(_) => f.debug_tuple("Column(..)").finish(),
-            Doc::Nesting(_) => f.debug_tuple("Nesting(..)").finish(),
+            Doc::Column(ref closure) => fmt_closure(f, "Column", &**closure),
+            Doc::Nesting(ref closure) => fmt_closure(f, "Nesting", &**closure),
+            Doc::PageWidth(ref closure) => fmt_closure(f, "PageWidth", &**closure),
+            Doc::Lazy(ref closure) => fmt_closure(f, "Lazy", &**closure),
+        }
+    }
+}
+
+/// Closures are identified by their address
+pub(crate) fn closure_address<F>(f: &F) -> *const ()
+where
+    F: ?Sized,
+{
+    f as *const F as *const ()
+}
+
+fn fmt_closure<F>(f: &mut fmt::Formatter<'_>, name: &str, closure: &F) -> fmt::Result
+where
+    F: ?Sized,
+{
+    f.debug_tuple(name)
+        .field(&closure_address(closure))
+        .finish()
+}
+
+impl<'a, T, A> Doc<'a, T, A>
+where
+    T: DocPtr<'a, A>,
+{
+    fn as_text(&self) -> Option<&str> {
+        match *self {
+            Doc::OwnedText(ref s) => Some(s),
+            Doc::BorrowedText(s) => Some(s),
+            Doc::SmallText(ref s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Moves the documents contained in `self` to `children`, leaving `Nil` in its place
+    fn take_children(&mut self, children: &mut Vec<T>) {
+        match mem::replace(self, Doc::Nil) {
+            Doc::Append(l, r) | Doc::FlatAlt(l, r) | Doc::Union(l, r) => {
+                children.push(l);
+                children.push(r);
+            }
+            Doc::Group(doc) | Doc::Nest(_, doc) | Doc::Annotated(_, doc) => children.push(doc),
+            _ => {}
+        }
+    }
+}
+
+/// Structural equality. Text is compared by content, regardless of how it is stored, and the
+/// closures of `Column`, `Nesting`, `PageWidth` and `Lazy` are compared by address.
+impl<'a, T, A> PartialEq for Doc<'a, T, A>
+where
+    T: DocPtr<'a, A>,
+    A: PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        // Documents are often deeply nested so compare them without recursion
+        let mut stack = vec![(self, other)];
+        while let Some(docs) = stack.pop() {
+            match docs {
+                (Doc::Nil, Doc::Nil) | (Doc::Line, Doc::Line) => {}
+                (Doc::Append(l1, r1), Doc::Append(l2, r2))
+                | (Doc::FlatAlt(l1, r1), Doc::FlatAlt(l2, r2))
+                | (Doc::Union(l1, r1), Doc::Union(l2, r2)) => {
+                    stack.push((r1, r2));
+                    stack.push((l1, l2));
+                }
+                (Doc::Group(l), Doc::Group(r)) => stack.push((l, r)),
+                (Doc::Nest(o1, l), Doc::Nest(o2, r)) if o1 == o2 => stack.push((l, r)),
+                (Doc::Annotated(a1, l), Doc::Annotated(a2, r)) if a1 == a2 => stack.push((l, r)),
+                (Doc::Column(l), Doc::Column(r))
+                | (Doc::Nesting(l), Doc::Nesting(r))
+                | (Doc::PageWidth(l), Doc::PageWidth(r))
+                | (Doc::Lazy(l), Doc::Lazy(r))
+                    if closure_address(&**l) == closure_address(&**r) => {}
+                (l, r) => match (l.as_text(), r.as_text()) {
+                    (Some(l), Some(r)) if l == r => {}
+                    _ => return false,
+                },
+            }
+        }
+        true
+    }
+}
+
+impl<'a, T, A> Eq for Doc<'a, T, A>
+where
+    T: DocPtr<'a, A>,
+    A: Eq,
+{
+}
+
+/// Hashes the structure of the document, consistently with `PartialEq`
+impl<'a, T, A> Hash for Doc<'a, T, A>
+where
+    T: DocPtr<'a, A>,
+    A: Hash,
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let mut stack = vec![self];
+        while let Some(doc) = stack.pop() {
+            if let Some(text) = doc.as_text() {
+                "text".hash(state);
+                text.hash(state);
+                continue;
+            }
+            mem::discriminant(doc).hash(state);
+            match *doc {
+                Doc::Nil | Doc::Line => {}
+                Doc::OwnedText(_) | Doc::BorrowedText(_) | Doc::SmallText(_) => unreachable!(),
+                Doc::Append(ref l, ref r)
+                | Doc::FlatAlt(ref l, ref r)
+                | Doc::Union(ref l, ref r) => {
+                    stack.push(r);
+                    stack.push(l);
+                }
+                Doc::Group(ref doc) => stack.push(doc),
+                Doc::Nest(offset, ref doc) => {
+                    offset.hash(state);
+                    stack.push(doc);
+                }
+                Doc::Annotated(ref ann, ref doc) => {
+                    ann.hash(state);
+                    stack.push(doc);
+                }
+                Doc::Column(ref f)
+                | Doc::Nesting(ref f)
+                | Doc::PageWidth(ref f)
+                | Doc::Lazy(ref f) => closure_address(&**f).hash(state),
+            }
         }
     }
 }
@@ -216,6 +391,37 @@ macro_rules! impl_doc {
             }
         }
 
+        impl<'a, A> PartialEq for $name<'a, A>
+        where
+            A: PartialEq,
+        {
+            fn eq(&self, other: &Self) -> bool {
+                *self.0 == *other.0
+            }
+        }
+
+        impl<'a, A> Eq for $name<'a, A> where A: Eq {}
+
+        impl<'a, A> Hash for $name<'a, A>
+        where
+            A: Hash,
+        {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                self.0.hash(state)
+            }
+        }
+
+        /// Drops the document without recursion, as documents may be too deep to drop recursively
+        impl<'a, A> Drop for $name<'a, A> {
+            fn drop(&mut self) {
+                let mut children = Vec::new();
+                self.0.take_children(&mut children);
+                while let Some(mut doc) = children.pop() {
+                    doc.0.take_children(&mut children);
+                }
+            }
+        }
+
         impl<'a, A> $name<'a, A> {
             pub fn new(doc: Doc<'a, $name<'a, A>, A>) -> $name<'a, A> {
                 $name(Box::new(doc))
@@ -310,6 +516,83 @@ macro_rules! impl_doc {
                 $allocator.intersperse(docs, separator).into_doc()
             }
 
+            /// A single document concatenating the given documents, separated by `space`.
+            #[inline]
+            pub fn hsep<I>(docs: I) -> Self
+            where
+                I: IntoIterator,
+                I::Item: Into<BuildDoc<'a, Self, A>>,
+            {
+                $allocator.hsep(docs).into_doc()
+            }
+
+            /// A single document concatenating the given documents, separated by `line`.
+            #[inline]
+            pub fn vsep<I>(docs: I) -> Self
+            where
+                I: IntoIterator,
+                I::Item: Into<BuildDoc<'a, Self, A>>,
+            {
+                $allocator.vsep(docs).into_doc()
+            }
+
+            /// A single document concatenating the given documents, separated by `space` if they
+            /// fit on a single line and by newlines otherwise.
+            #[inline]
+            pub fn sep<I>(docs: I) -> Self
+            where
+                I: IntoIterator,
+                I::Item: Into<BuildDoc<'a, Self, A>>,
+            {
+                $allocator.sep(docs).into_doc()
+            }
+
+            /// A single document concatenating the given documents without any separator.
+            #[inline]
+            pub fn hcat<I>(docs: I) -> Self
+            where
+                I: IntoIterator,
+                I::Item: Into<BuildDoc<'a, Self, A>>,
+            {
+                $allocator.hcat(docs).into_doc()
+            }
+
+            /// A single document concatenating the given documents, separated by `line_`.
+            #[inline]
+            pub fn vcat<I>(docs: I) -> Self
+            where
+                I: IntoIterator,
+                I::Item: Into<BuildDoc<'a, Self, A>>,
+            {
+                $allocator.vcat(docs).into_doc()
+            }
+
+            /// A single document concatenating the given documents directly if they fit on a
+            /// single line and separated by newlines otherwise.
+            #[inline]
+            pub fn cat<I>(docs: I) -> Self
+            where
+                I: IntoIterator,
+                I::Item: Into<BuildDoc<'a, Self, A>>,
+            {
+                $allocator.cat(docs).into_doc()
+            }
+
+            /// Appends `separator` to every document except the last.
+            #[inline]
+            pub fn punctuate<I, S>(docs: I, separator: S) -> Vec<Self>
+            where
+                I: IntoIterator,
+                I::Item: Into<BuildDoc<'a, Self, A>>,
+                S: Into<BuildDoc<'a, Self, A>> + Clone,
+            {
+                $allocator
+                    .punctuate(docs, separator)
+                    .into_iter()
+                    .map(DocBuilder::into_doc)
+                    .collect()
+            }
+
             /// Acts as `self` when laid out on multiple lines and acts as `that` when laid out on a single line.
             #[inline]
             pub fn flat_alt<D>(self, doc: D) -> Self
@@ -352,6 +635,15 @@ macro_rules! impl_doc {
             {
                 DocBuilder(&$allocator, self.into()).union(other).into_doc()
             }
+
+            /// A simplified copy of this document. See `DocAllocator::simplify`.
+            #[inline]
+            pub fn simplify(&self) -> Self
+            where
+                A: Clone,
+            {
+                $allocator.simplify(self).into_doc()
+            }
         }
     };
 }
@@ -365,7 +657,7 @@ impl fmt::Write for FmtText {
     fn write_str(&mut self, s: &str) -> fmt::Result {
         match self {
             FmtText::Small(buf) => {
-                if let Err(_) = buf.try_push_str(s) {
+                if buf.try_push_str(s).is_err() {
                     let mut new_str = String::with_capacity(buf.len() + s.len());
                     new_str.push_str(buf);
                     new_str.push_str(s);
@@ -460,7 +752,7 @@ where
     where
         D: Into<BuildDoc<'a, T, A>>,
     {
-        DocBuilder(&T::ALLOCATOR, self.into()).flat_alt(doc).1
+        DocBuilder(&T::ALLOCATOR, self).flat_alt(doc).1
     }
 }
 
@@ -496,7 +788,12 @@ where
     }
 }
 
-pub struct Pretty<'a, 'd, T, A>
+/// Formats a document with `Display`, returned by `Doc::pretty`.
+///
+/// This type used to be called `Pretty`. That name now belongs to the `Pretty` trait, which lives in
+/// the same namespace, so code naming the type must use `PrettyFmt` instead. This is a breaking
+/// change released in 0.9.
+pub struct PrettyFmt<'a, 'd, T, A>
 where
     A: 'a,
     T: DocPtr<'a, A> + 'a,
@@ -505,7 +802,7 @@ where
     width: usize,
 }
 
-impl<'a, T, A> fmt::Display for Pretty<'a, '_, T, A>
+impl<'a, T, A> fmt::Display for PrettyFmt<'a, '_, T, A>
 where
     T: DocPtr<'a, A>,
 {
@@ -527,6 +824,26 @@ where
         self.render_raw(width, &mut IoWrite::new(out))
     }
 
+    /// Writes a rendered document to a `futures::io::AsyncWrite` object. The output is written in
+    /// chunks as the document is laid out.
+    #[cfg(feature = "async")]
+    pub async fn render_async<W>(&self, width: usize, out: &mut W) -> io::Result<()>
+    where
+        W: ?Sized + futures_io::AsyncWrite + Unpin,
+    {
+        render_async::render(self, width, render_async::FuturesWrite(out)).await
+    }
+
+    /// Writes a rendered document to a `tokio::io::AsyncWrite` object. The output is written in
+    /// chunks as the document is laid out.
+    #[cfg(feature = "tokio")]
+    pub async fn render_tokio<W>(&self, width: usize, out: &mut W) -> io::Result<()>
+    where
+        W: ?Sized + tokio::io::AsyncWrite + Unpin,
+    {
+        render_async::render(self, width, render_async::TokioWrite(out)).await
+    }
+
     /// Writes a rendered document to a `std::fmt::Write` object.
     #[inline]
     pub fn render_fmt<W>(&self, width: usize, out: &mut W) -> fmt::Result
@@ -536,13 +853,177 @@ where
         self.render_raw(width, &mut FmtWrite::new(out))
     }
 
+    /// Returns an indented tree view of the structure of the document, with the closures of
+    /// `Column`, `Nesting`, `PageWidth` and `Lazy` shown by address.
+    ///
+    /// ```
+    /// use pretty::BoxDoc;
+    /// let doc = BoxDoc::<()>::text("a").append(BoxDoc::line()).group();
+    /// assert_eq!(
+    ///     doc.dump(),
+    ///     "Group\n  Append\n    Text(\"a\")\n    FlatAlt\n      Line\n      Text(\" \")"
+    /// );
+    /// ```
+    pub fn dump(&self) -> String
+    where
+        A: fmt::Debug,
+    {
+        dump::dump(self)
+    }
+
+    /// Returns the structure of the document as a Graphviz DOT graph. Documents which are shared
+    /// by arena allocated documents (`RefDoc`) are only included once. `BoxDoc` and `RcDoc` own
+    /// their children, so reused parts are included each time they appear.
+    pub fn to_dot(&self) -> String
+    where
+        A: fmt::Debug,
+    {
+        dot::to_dot(self)
+    }
+
+    /// Renders the document, calling `trace` with each decision made to lay out a `group` or a
+    /// `union`, including the position of the document and where the check of whether it fits
+    /// failed.
+    ///
+    /// ```
+    /// use pretty::{BoxDoc, Choice, RenderOptions};
+    ///
+    /// let doc = BoxDoc::<()>::text("hello").append(BoxDoc::line()).append("world").group();
+    /// let mut s = String::new();
+    /// let mut decisions = Vec::new();
+    /// doc.render_traced(&RenderOptions::new(8), &mut s, |decision| decisions.push(decision.clone()))
+    ///     .unwrap();
+    /// assert_eq!(decisions.len(), 1);
+    /// assert_eq!(decisions[0].choice, Choice::Break);
+    /// assert_eq!(decisions[0].failed_at, Some(11));
+    /// ```
+    #[inline]
+    pub fn render_traced<W, F>(
+        &self,
+        options: &RenderOptions,
+        out: &mut W,
+        mut trace: F,
+    ) -> Result<RenderStats, W::Error>
+    where
+        W: ?Sized + render::RenderAnnotated<A>,
+        F: FnMut(&Decision),
+    {
+        render::traced(self, options, out, &mut trace)
+    }
+
+    /// Renders the document in `width` columns, following each line of the output with a line
+    /// for each `group` or `union` laid out on it. A `^` marks the column the document starts at,
+    /// followed by the layout that was chosen and why.
+    ///
+    /// ```
+    /// use pretty::BoxDoc;
+    ///
+    /// let doc = BoxDoc::<()>::text("hello").append(BoxDoc::line()).append("world").group();
+    /// assert_eq!(
+    ///     doc.explain(8),
+    ///     "hello\n^ group break: reached column 11 of 8\nworld"
+    /// );
+    /// ```
+    pub fn explain(&self, width: usize) -> String {
+        explain::explain(self, width)
+    }
+
+    /// Renders the document to a `String`, which is preallocated using an estimate of the size of
+    /// the output.
+    ///
+    /// ```
+    /// use pretty::BoxDoc;
+    /// let doc = BoxDoc::<()>::group(
+    ///     BoxDoc::text("hello").append(BoxDoc::line()).append(BoxDoc::text("world"))
+    /// );
+    /// assert_eq!(doc.render_string(80), "hello world");
+    /// assert_eq!(doc.render_string(5), "hello\nworld");
+    /// ```
+    #[inline]
+    pub fn render_string(&self, width: usize) -> String {
+        let mut out = String::with_capacity(render::estimate_size(self));
+        match self.render_raw(width, &mut out) {
+            Ok(()) => out,
+            Err(never) => match never {},
+        }
+    }
+
+    /// Renders the document to UTF-8 encoded bytes, which are preallocated using an estimate of
+    /// the size of the output.
+    #[inline]
+    pub fn render_vec(&self, width: usize) -> Vec<u8> {
+        let mut out = Vec::with_capacity(render::estimate_size(self));
+        match self.render_raw(width, &mut out) {
+            Ok(()) => out,
+            Err(never) => match never {},
+        }
+    }
+
     /// Writes a rendered document to a `RenderAnnotated<A>` object.
     #[inline]
     pub fn render_raw<W>(&self, width: usize, out: &mut W) -> Result<(), W::Error>
     where
         W: ?Sized + render::RenderAnnotated<A>,
     {
-        render::best(self, width, out)
+        render::best(self, &RenderOptions::new(width), out).map(|_| ())
+    }
+
+    /// Writes a rendered document to a `RenderAnnotated<A>` object, limiting the output as
+    /// specified by `options`. Returns statistics about the layout, including the lines which
+    /// could not be made to fit.
+    ///
+    /// ```
+    /// use pretty::{Arena, DocAllocator, FmtWrite, Overflow, RenderOptions};
+    ///
+    /// let arena = Arena::<()>::new();
+    /// let doc = arena.intersperse(vec!["first", "second", "third"], arena.hardline());
+    /// let mut s = String::new();
+    /// let stats = doc
+    ///     .1
+    ///     .render_with_options(&RenderOptions::new(5), &mut FmtWrite::new(&mut s))
+    ///     .unwrap();
+    /// assert_eq!(stats.lines, 3);
+    /// assert_eq!(stats.max_width, 6);
+    /// assert_eq!(stats.overflows, vec![Overflow { line: 1, width: 6 }]);
+    ///
+    /// let mut s = String::new();
+    /// doc.1
+    ///     .render_with_options(
+    ///         &RenderOptions::new(80).max_lines(2).max_line_chars(4),
+    ///         &mut FmtWrite::new(&mut s),
+    ///     )
+    ///     .unwrap();
+    /// assert_eq!(s, "firs…\nseco…\n…");
+    /// ```
+    #[inline]
+    pub fn render_with_options<W>(
+        &self,
+        options: &RenderOptions,
+        out: &mut W,
+    ) -> Result<RenderStats, W::Error>
+    where
+        W: ?Sized + render::RenderAnnotated<A>,
+    {
+        render::best(self, options, out)
+    }
+
+    /// Returns the number of lines of `self` when laid out in `width` columns. The document is laid
+    /// out without writing it anywhere.
+    ///
+    /// ```
+    /// use pretty::{Arena, DocAllocator};
+    ///
+    /// let arena = Arena::<()>::new();
+    /// let doc = arena.reflow("one two three");
+    /// assert_eq!(doc.1.line_count(80), 1);
+    /// assert_eq!(doc.1.line_count(8), 2);
+    /// ```
+    #[inline]
+    pub fn line_count(&self, width: usize) -> usize {
+        match render::best(self, &RenderOptions::new(width), &mut render::Discard) {
+            Ok(stats) => stats.lines,
+            Err(never) => match never {},
+        }
     }
 
     /// Returns a value which implements `std::fmt::Display`
@@ -555,8 +1036,8 @@ where
     /// assert_eq!(format!("{}", doc.pretty(80)), "hello world");
     /// ```
     #[inline]
-    pub fn pretty<'d>(&'d self, width: usize) -> Pretty<'a, 'd, T, A> {
-        Pretty { doc: self, width }
+    pub fn pretty<'d>(&'d self, width: usize) -> PrettyFmt<'a, 'd, T, A> {
+        PrettyFmt { doc: self, width }
     }
 }
 
@@ -570,7 +1051,7 @@ where
     where
         W: WriteColor,
     {
-        render::best(self, width, &mut TermColored::new(out))
+        render::best(self, &RenderOptions::new(width), &mut TermColored::new(out)).map(|_| ())
     }
 }
 
@@ -591,12 +1072,12 @@ where
     }
 }
 
-impl<'a, D, A> Into<BuildDoc<'a, D::Doc, A>> for DocBuilder<'a, D, A>
+impl<'a, D, A> From<DocBuilder<'a, D, A>> for BuildDoc<'a, D::Doc, A>
 where
     D: ?Sized + DocAllocator<'a, A>,
 {
-    fn into(self) -> BuildDoc<'a, D::Doc, A> {
-        self.1
+    fn from(val: DocBuilder<'a, D, A>) -> Self {
+        val.1
     }
 }
 
@@ -715,14 +1196,15 @@ where
         DocBuilder(self, Doc::text(data).into())
     }
 
-    /// Allocate a document concatenating the given documents.
+    /// Allocate a document concatenating the given documents. The documents are appended as a
+    /// balanced tree, so long concatenations do not create deeply nested documents.
     #[inline]
     fn concat<I>(&'a self, docs: I) -> DocBuilder<'a, Self, A>
     where
         I: IntoIterator,
         I::Item: Into<BuildDoc<'a, Self::Doc, A>>,
     {
-        docs.into_iter().fold(self.nil(), |a, b| a.append(b))
+        concat_balanced(self, docs.into_iter().map(Into::into))
     }
 
     /// Allocate a document that intersperses the given separator `S` between the given documents
@@ -739,66 +1221,501 @@ where
         I::Item: Into<BuildDoc<'a, Self::Doc, A>>,
         S: Into<BuildDoc<'a, Self::Doc, A>> + Clone,
     {
-        let mut result = self.nil();
-        let mut iter = docs.into_iter();
-
-        if let Some(first) = iter.next() {
-            result = result.append(first);
-
-            for doc in iter {
-                result = result.append(separator.clone());
-                result = result.append(doc);
-            }
-        }
+        separate_with(self, docs, || DocBuilder(self, separator.clone().into()))
+    }
 
-        result
+    /// Allocate a document that concatenates the given documents, separated by `space`.
+    ///
+    /// ```
+    /// use pretty::{Arena, DocAllocator};
+    ///
+    /// let arena = Arena::<()>::new();
+    /// let doc = arena.hsep(vec!["a", "b", "c"]);
+    /// assert_eq!(doc.1.pretty(1).to_string(), "a b c");
+    /// ```
+    #[inline]
+    fn hsep<I>(&'a self, docs: I) -> DocBuilder<'a, Self, A>
+    where
+        I: IntoIterator,
+        I::Item: Into<BuildDoc<'a, Self::Doc, A>>,
+    {
+        separate_with(self, docs, || self.space())
     }
 
-    /// Allocate a document that acts differently based on the position and page layout
+    /// Allocate a document that concatenates the given documents, separated by `line`.
     ///
-    /// ```rust
-    /// use pretty::DocAllocator;
+    /// Since the result is not grouped, each document is put on its own line unless an enclosing
+    /// group is laid out on a single line.
     ///
-    /// let arena = pretty::Arena::<()>::new();
-    /// let doc = arena.text("prefix ")
-    ///     .append(arena.column(|l| {
-    ///         arena.text("| <- column ").append(arena.as_string(l)).into_doc()
-    ///     }));
-    /// assert_eq!(doc.1.pretty(80).to_string(), "prefix | <- column 7");
+    /// ```
+    /// use pretty::{Arena, DocAllocator};
+    ///
+    /// let arena = Arena::<()>::new();
+    /// let doc = arena.vsep(vec!["a", "b", "c"]);
+    /// assert_eq!(doc.1.pretty(80).to_string(), "a\nb\nc");
     /// ```
     #[inline]
-    fn column(&'a self, f: impl Fn(usize) -> Self::Doc + 'a) -> DocBuilder<'a, Self, A> {
-        DocBuilder(self, Doc::Column(self.alloc_column_fn(f)).into())
+    fn vsep<I>(&'a self, docs: I) -> DocBuilder<'a, Self, A>
+    where
+        I: IntoIterator,
+        I::Item: Into<BuildDoc<'a, Self::Doc, A>>,
+    {
+        separate_with(self, docs, || self.line())
     }
 
-    /// Allocate a document that acts differently based on the current nesting level
+    /// Allocate a document that concatenates the given documents, separated by `space` if they
+    /// fit on a single line and by newlines otherwise (`vsep(docs).group()`).
     ///
-    /// ```rust
-    /// use pretty::DocAllocator;
+    /// ```
+    /// use pretty::{Arena, DocAllocator};
     ///
-    /// let arena = pretty::Arena::<()>::new();
-    /// let doc = arena.text("prefix ")
-    ///     .append(arena.nesting(|l| {
-    ///         arena.text("[Nested: ").append(arena.as_string(l)).append("]").into_doc()
-    ///     }).nest(4));
-    /// assert_eq!(doc.1.pretty(80).to_string(), "prefix [Nested: 4]");
+    /// let arena = Arena::<()>::new();
+    /// let doc = arena.sep(vec!["a", "b", "c"]);
+    /// assert_eq!(doc.1.pretty(80).to_string(), "a b c");
+    /// assert_eq!(doc.1.pretty(3).to_string(), "a\nb\nc");
     /// ```
     #[inline]
-    fn nesting(&'a self, f: impl Fn(usize) -> Self::Doc + 'a) -> DocBuilder<'a, Self, A> {
-        DocBuilder(self, Doc::Nesting(self.alloc_column_fn(f)).into())
+    fn sep<I>(&'a self, docs: I) -> DocBuilder<'a, Self, A>
+    where
+        I: IntoIterator,
+        I::Item: Into<BuildDoc<'a, Self::Doc, A>>,
+    {
+        self.vsep(docs).group()
     }
 
-    /// Reflows `text` inserting `softline` in place of any whitespace
+    /// Allocate a document that concatenates the given documents without any separator. Same as
+    /// `concat`.
     #[inline]
-    fn reflow(&'a self, text: &'a str) -> DocBuilder<'a, Self, A>
+    fn hcat<I>(&'a self, docs: I) -> DocBuilder<'a, Self, A>
     where
-        Self: Sized,
-        Self::Doc: Clone,
-        A: Clone,
+        I: IntoIterator,
+        I::Item: Into<BuildDoc<'a, Self::Doc, A>>,
     {
-        self.intersperse(text.split(char::is_whitespace), self.line().group())
+        self.concat(docs)
     }
-}
+
+    /// Allocate a document that concatenates the given documents, separated by `line_`.
+    ///
+    /// ```
+    /// use pretty::{Arena, DocAllocator};
+    ///
+    /// let arena = Arena::<()>::new();
+    /// let doc = arena.vcat(vec!["a", "b", "c"]);
+    /// assert_eq!(doc.1.pretty(80).to_string(), "a\nb\nc");
+    /// assert_eq!(doc.group().1.pretty(80).to_string(), "abc");
+    /// ```
+    #[inline]
+    fn vcat<I>(&'a self, docs: I) -> DocBuilder<'a, Self, A>
+    where
+        I: IntoIterator,
+        I::Item: Into<BuildDoc<'a, Self::Doc, A>>,
+    {
+        separate_with(self, docs, || self.line_())
+    }
+
+    /// Allocate a document that concatenates the given documents directly if they fit on a single
+    /// line and separated by newlines otherwise (`vcat(docs).group()`).
+    #[inline]
+    fn cat<I>(&'a self, docs: I) -> DocBuilder<'a, Self, A>
+    where
+        I: IntoIterator,
+        I::Item: Into<BuildDoc<'a, Self::Doc, A>>,
+    {
+        self.vcat(docs).group()
+    }
+
+    /// Appends `separator` to every document except the last.
+    ///
+    /// NOTE: The separator type, `S` may need to be cloned. Consider using cheaply cloneable ptr
+    /// like `RefDoc` or `RcDoc`
+    ///
+    /// ```
+    /// use pretty::{Arena, DocAllocator};
+    ///
+    /// let arena = Arena::<()>::new();
+    /// let docs = arena.punctuate(vec!["a", "b", "c"], ",");
+    /// assert_eq!(arena.sep(docs).1.pretty(80).to_string(), "a, b, c");
+    /// ```
+    #[inline]
+    fn punctuate<I, S>(&'a self, docs: I, separator: S) -> Vec<DocBuilder<'a, Self, A>>
+    where
+        I: IntoIterator,
+        I::Item: Into<BuildDoc<'a, Self::Doc, A>>,
+        S: Into<BuildDoc<'a, Self::Doc, A>> + Clone,
+    {
+        let mut iter = docs.into_iter().peekable();
+        let mut result = Vec::new();
+        while let Some(doc) = iter.next() {
+            let doc = self.nil().append(doc);
+            result.push(if iter.peek().is_some() {
+                doc.append(separator.clone())
+            } else {
+                doc
+            });
+        }
+        result
+    }
+
+    /// Allocate a document which puts `docs` between `open` and `close`, separated by `separator`.
+    /// If the documents do not fit on a single line each one is put on its own line, nested one
+    /// level deeper than `open` and `close`.
+    ///
+    /// NOTE: The separator type, `S` may need to be cloned. Consider using cheaply cloneable ptr
+    /// like `RefDoc` or `RcDoc`
+    ///
+    /// ```
+    /// use pretty::{Arena, DocAllocator};
+    ///
+    /// let arena = Arena::<()>::new();
+    /// let doc = arena.enclose_sep("<", ">", ";", vec!["a", "b"]);
+    /// assert_eq!(doc.1.pretty(80).to_string(), "<a; b>");
+    /// assert_eq!(doc.1.pretty(5).to_string(), "<\n    a;\n    b\n>");
+    /// ```
+    #[inline]
+    fn enclose_sep<I, O, C, S>(
+        &'a self,
+        open: O,
+        close: C,
+        separator: S,
+        docs: I,
+    ) -> DocBuilder<'a, Self, A>
+    where
+        I: IntoIterator,
+        I::Item: Into<BuildDoc<'a, Self::Doc, A>>,
+        O: Into<BuildDoc<'a, Self::Doc, A>>,
+        C: Into<BuildDoc<'a, Self::Doc, A>>,
+        S: Into<BuildDoc<'a, Self::Doc, A>> + Clone,
+    {
+        self.delimited(open, separator, close, TrailingComma::Never)
+            .build(docs)
+    }
+
+    /// Allocate a comma separated tuple, `(a, b, c)`. See `enclose_sep`.
+    ///
+    /// ```
+    /// use pretty::{Arena, DocAllocator};
+    ///
+    /// let arena = Arena::<()>::new();
+    /// let doc = arena.tupled(vec!["a", "b"]);
+    /// assert_eq!(doc.1.pretty(80).to_string(), "(a, b)");
+    /// assert_eq!(doc.1.pretty(5).to_string(), "(\n    a,\n    b\n)");
+    /// ```
+    #[inline]
+    fn tupled<I>(&'a self, docs: I) -> DocBuilder<'a, Self, A>
+    where
+        I: IntoIterator,
+        I::Item: Into<BuildDoc<'a, Self::Doc, A>>,
+    {
+        self.enclose_sep("(", ")", ",", docs)
+    }
+
+    /// Allocate a comma separated list, `[a, b, c]`. See `enclose_sep`.
+    #[inline]
+    fn list<I>(&'a self, docs: I) -> DocBuilder<'a, Self, A>
+    where
+        I: IntoIterator,
+        I::Item: Into<BuildDoc<'a, Self::Doc, A>>,
+    {
+        self.enclose_sep("[", "]", ",", docs)
+    }
+
+    /// Returns a builder which puts documents between `open` and `close`, separated by
+    /// `separator`. The `trailing_comma` argument controls whether `separator` is also inserted
+    /// after the last document.
+    ///
+    /// ```
+    /// use pretty::{Arena, DocAllocator, TrailingComma};
+    ///
+    /// let arena = Arena::<()>::new();
+    /// let doc = arena
+    ///     .delimited("{", ",", "}", TrailingComma::IfBroken)
+    ///     .nest(2)
+    ///     .padded()
+    ///     .build(vec!["a", "b"]);
+    /// assert_eq!(doc.1.pretty(80).to_string(), "{ a, b }");
+    /// assert_eq!(doc.1.pretty(5).to_string(), "{\n  a,\n  b,\n}");
+    /// ```
+    #[inline]
+    fn delimited<O, S, C>(
+        &'a self,
+        open: O,
+        separator: S,
+        close: C,
+        trailing_comma: TrailingComma,
+    ) -> Delimited<'a, Self, A, S>
+    where
+        O: Into<BuildDoc<'a, Self::Doc, A>>,
+        C: Into<BuildDoc<'a, Self::Doc, A>>,
+    {
+        Delimited {
+            allocator: self,
+            open: open.into(),
+            separator,
+            close: close.into(),
+            trailing_comma,
+            nest: 4,
+            padded: false,
+        }
+    }
+
+    /// Returns a builder which lays out `rows` as a table, padding the cells so that the columns
+    /// line up. If the table is too wide to fit, each cell is put on its own line instead.
+    ///
+    /// ```
+    /// use pretty::{Alignment, Arena, DocAllocator};
+    ///
+    /// let arena = Arena::<()>::new();
+    /// let doc = arena
+    ///     .table(vec![vec!["x", "1"], vec!["total", "100"]])
+    ///     .align(1, Alignment::Right)
+    ///     .build();
+    /// assert_eq!(doc.1.pretty(80).to_string(), "x       1\ntotal 100");
+    /// assert_eq!(doc.1.pretty(5).to_string(), "x\n    1\ntotal\n    100");
+    /// ```
+    #[inline]
+    fn table<R, I>(&'a self, rows: R) -> Table<'a, Self, A>
+    where
+        R: IntoIterator<Item = I>,
+        I: IntoIterator,
+        I::Item: Into<BuildDoc<'a, Self::Doc, A>>,
+    {
+        let rows = rows
+            .into_iter()
+            .map(|row| {
+                row.into_iter()
+                    .map(|cell| DocBuilder(self, cell.into()).into_doc())
+                    .collect()
+            })
+            .collect();
+        Table::new(self, rows)
+    }
+
+    /// Allocate a document that acts differently based on the position and page layout
+    ///
+    /// ```rust
+    /// use pretty::DocAllocator;
+    ///
+    /// let arena = pretty::Arena::<()>::new();
+    /// let doc = arena.text("prefix ")
+    ///     .append(arena.column(|l| {
+    ///         arena.text("| <- column ").append(arena.as_string(l)).into_doc()
+    ///     }));
+    /// assert_eq!(doc.1.pretty(80).to_string(), "prefix | <- column 7");
+    /// ```
+    #[inline]
+    fn column(&'a self, f: impl Fn(usize) -> Self::Doc + 'a) -> DocBuilder<'a, Self, A> {
+        DocBuilder(self, Doc::Column(self.alloc_column_fn(f)).into())
+    }
+
+    /// Allocate a document that acts differently based on the current nesting level
+    ///
+    /// ```rust
+    /// use pretty::DocAllocator;
+    ///
+    /// let arena = pretty::Arena::<()>::new();
+    /// let doc = arena.text("prefix ")
+    ///     .append(arena.nesting(|l| {
+    ///         arena.text("[Nested: ").append(arena.as_string(l)).append("]").into_doc()
+    ///     }).nest(4));
+    /// assert_eq!(doc.1.pretty(80).to_string(), "prefix [Nested: 4]");
+    /// ```
+    #[inline]
+    fn nesting(&'a self, f: impl Fn(usize) -> Self::Doc + 'a) -> DocBuilder<'a, Self, A> {
+        DocBuilder(self, Doc::Nesting(self.alloc_column_fn(f)).into())
+    }
+
+    /// Allocate a document that acts differently based on the width it is rendered with
+    ///
+    /// ```rust
+    /// use pretty::DocAllocator;
+    ///
+    /// let arena = pretty::Arena::<()>::new();
+    /// let doc = arena.page_width(|w| arena.text("width: ").append(arena.as_string(w)).into_doc());
+    /// assert_eq!(doc.1.pretty(80).to_string(), "width: 80");
+    /// ```
+    #[inline]
+    fn page_width(&'a self, f: impl Fn(usize) -> Self::Doc + 'a) -> DocBuilder<'a, Self, A> {
+        DocBuilder(self, Doc::PageWidth(self.alloc_column_fn(f)).into())
+    }
+
+    /// Allocate a document which is built by `f` once the renderer reaches it. The document is
+    /// built at most once and reused by later renders, so parts of a large document which are
+    /// never rendered (for instance because the output is limited by `RenderOptions`) are never
+    /// built.
+    ///
+    /// ```rust
+    /// use pretty::{DocAllocator, RenderOptions};
+    ///
+    /// let arena = pretty::Arena::<()>::new();
+    /// let doc = arena.text("head")
+    ///     .append(arena.hardline())
+    ///     .append(arena.lazy(|| -> String { unreachable!() }));
+    /// let mut s = String::new();
+    /// doc.1.render_with_options(&RenderOptions::new(80).max_lines(1), &mut pretty::FmtWrite::new(&mut s)).unwrap();
+    /// assert_eq!(s, "head\n…");
+    /// ```
+    #[inline]
+    fn lazy<F, T>(&'a self, f: F) -> DocBuilder<'a, Self, A>
+    where
+        F: FnOnce() -> T + 'a,
+        T: Into<BuildDoc<'a, Self::Doc, A>>,
+        Self::Doc: Clone,
+    {
+        let f = Cell::new(Some(f));
+        let doc = OnceCell::new();
+        let f = self.alloc_column_fn(move |_| {
+            doc.get_or_init(|| {
+                let f = f.take().expect("lazy document is built once");
+                self.alloc_cow(f().into())
+            })
+            .clone()
+        });
+        DocBuilder(self, Doc::Lazy(f).into())
+    }
+
+    /// Allocate a simplified copy of `doc` which renders the same output faster. `Append` chains
+    /// are flattened, adjacent text is merged when it is short enough to be stored inline, and
+    /// `Group` and `Nest` documents which have no effect (nested groups, groups without a
+    /// `flat_alt` and nests without a line break) are removed.
+    ///
+    /// ```rust
+    /// use pretty::{Doc, DocAllocator};
+    ///
+    /// let arena = pretty::Arena::<()>::new();
+    /// let doc = arena.text("a").append("b").nest(2).group().group().append(arena.text("c"));
+    /// let simplified = arena.simplify(&doc.1);
+    /// assert_eq!(*simplified.1, Doc::text("abc"));
+    /// assert_eq!(simplified.1.pretty(80).to_string(), doc.1.pretty(80).to_string());
+    /// ```
+    #[inline]
+    fn simplify(&'a self, doc: &Doc<'a, Self::Doc, A>) -> DocBuilder<'a, Self, A>
+    where
+        A: Clone,
+    {
+        DocBuilder(self, simplify::simplify(self, doc).into())
+    }
+
+    /// Reflows `text` inserting `softline` in place of any whitespace
+    #[inline]
+    fn reflow(&'a self, text: &'a str) -> DocBuilder<'a, Self, A>
+    where
+        Self: Sized,
+        Self::Doc: Clone,
+        A: Clone,
+    {
+        self.intersperse(text.split(char::is_whitespace), self.line().group())
+    }
+
+    /// Lays out the words of `text` like `reflow`, but pads the spaces between the words so that
+    /// every line except the last one ends at the right margin.
+    ///
+    /// ```
+    /// use pretty::{Arena, DocAllocator};
+    ///
+    /// let arena = Arena::<()>::new();
+    /// let doc = arena.justify("Lorem ipsum dolor sit amet");
+    /// assert_eq!(doc.1.pretty(12).to_string(), "Lorem  ipsum\ndolor    sit\namet");
+    /// ```
+    #[inline]
+    fn justify<S>(&'a self, text: S) -> DocBuilder<'a, Self, A>
+    where
+        S: Into<Cow<'a, str>>,
+    {
+        justify::justify(self, text.into())
+    }
+
+    /// Returns a builder which reflows `text`, breaking lines only where the Unicode line breaking
+    /// algorithm (UAX #14) allows it. Paragraphs, separated by blank lines, are kept apart by an
+    /// empty line while any other whitespace may be replaced by a line break.
+    ///
+    /// ```
+    /// use pretty::{Arena, DocAllocator};
+    ///
+    /// let arena = Arena::<()>::new();
+    /// let doc = arena.wrap("Lorem ipsum dolor sit amet.\n\nSecond paragraph.").build();
+    /// assert_eq!(
+    ///     doc.1.pretty(12).to_string(),
+    ///     "Lorem ipsum\ndolor sit\namet.\n\nSecond\nparagraph."
+    /// );
+    /// ```
+    #[cfg(feature = "unicode-linebreak")]
+    #[inline]
+    fn wrap<S>(&'a self, text: S) -> Wrap<'a, Self, A>
+    where
+        S: Into<Cow<'a, str>>,
+    {
+        Wrap::new(self, text.into())
+    }
+}
+
+/// Allocates `n` spaces, borrowing them from a static string
+fn spaces<'a, D, A>(allocator: &'a D, n: usize) -> DocBuilder<'a, D, A>
+where
+    D: ?Sized + DocAllocator<'a, A>,
+    A: 'a,
+{
+    use crate::render::SPACES;
+    let mut doc = allocator.nil();
+    let mut remaining = n;
+    while remaining != 0 {
+        let i = SPACES.len().min(remaining);
+        remaining -= i;
+        doc = doc.append(allocator.text(&SPACES[..i]))
+    }
+    doc
+}
+
+/// Concatenates `docs`, placing a document created by `separator` between each of them
+fn separate_with<'a, D, A, I>(
+    allocator: &'a D,
+    docs: I,
+    mut separator: impl FnMut() -> DocBuilder<'a, D, A>,
+) -> DocBuilder<'a, D, A>
+where
+    D: ?Sized + DocAllocator<'a, A>,
+    A: 'a,
+    I: IntoIterator,
+    I::Item: Into<BuildDoc<'a, D::Doc, A>>,
+{
+    let mut docs = docs.into_iter().map(Into::into);
+    let first = docs.next();
+    concat_balanced(
+        allocator,
+        first
+            .into_iter()
+            .chain(docs.flat_map(|doc| [separator().1, doc])),
+    )
+}
+
+/// Concatenates `docs` into a balanced tree of `Append` documents, so that the depth of the
+/// document grows logarithmically with the number of documents. Long concatenations would
+/// otherwise create documents which are too deep to be dropped or cloned without overflowing the
+/// stack.
+fn concat_balanced<'a, D, A, I>(allocator: &'a D, docs: I) -> DocBuilder<'a, D, A>
+where
+    D: ?Sized + DocAllocator<'a, A>,
+    A: 'a,
+    I: IntoIterator<Item = BuildDoc<'a, D::Doc, A>>,
+{
+    // Each entry is a complete tree of `2^height` documents, with the heights decreasing towards
+    // the top of the stack. Trees of the same height are appended, like a carry in binary addition.
+    let mut trees: Vec<(u32, DocBuilder<'a, D, A>)> = Vec::new();
+    for doc in docs {
+        let mut tree = (0, DocBuilder(allocator, doc));
+        while let Some(&(height, _)) = trees.last() {
+            if height != tree.0 {
+                break;
+            }
+            let (_, left) = trees.pop().unwrap();
+            tree = (height + 1, left.append(tree.1));
+        }
+        trees.push(tree);
+    }
+    trees
+        .into_iter()
+        .rev()
+        .fold(allocator.nil(), |right, (_, left)| left.append(right))
+}
 
 /// Either a `Doc` or a pointer to a `Doc` (`D`)
 #[derive(Clone, Debug)]
@@ -860,7 +1777,68 @@ where
     }
 }
 
-impl<'a, 's, D, A> DocBuilder<'a, D, A>
+/// Trait for types which can be converted to a document.
+///
+/// Implementations for user defined types are usually written for a reference (`&'a T`) so that
+/// the fields can be borrowed for the lifetime of the document. With the `derive` feature enabled
+/// `#[derive(Pretty)]` generates such an implementation.
+///
+/// Implementations are provided for references to the primitive types, strings, `Option`,
+/// `Result`, tuples and the standard collections. These are laid out like `{:#?}` except that
+/// values which fit on the current line are kept on one line. Strings, characters and floating
+/// point numbers are formatted with `Debug`, so strings are quoted and escaped.
+///
+/// ```rust
+/// use pretty::{Arena, DocAllocator, DocBuilder, Pretty};
+///
+/// struct Call {
+///     name: String,
+///     args: Vec<String>,
+/// }
+///
+/// impl<'a, D, A> Pretty<'a, D, A> for &'a Call
+/// where
+///     D: DocAllocator<'a, A>,
+///     D::Doc: Clone,
+///     A: Clone + 'a,
+/// {
+///     fn pretty(self, allocator: &'a D) -> DocBuilder<'a, D, A> {
+///         let args = allocator.intersperse(
+///             self.args.iter().map(|arg| allocator.text(&arg[..])),
+///             allocator.text(",").append(allocator.line()),
+///         );
+///         allocator
+///             .text(&self.name[..])
+///             .append(allocator.line_().append(args).nest(4).append(allocator.line_()).parens())
+///             .group()
+///     }
+/// }
+///
+/// let arena = Arena::<()>::new();
+/// let call = Call { name: "f".into(), args: vec!["x".into(), "y".into()] };
+/// assert_eq!(call.pretty(&arena).1.pretty(80).to_string(), "f(x, y)");
+/// assert_eq!(call.pretty(&arena).1.pretty(5).to_string(), "f(\n    x,\n    y\n)");
+/// ```
+pub trait Pretty<'a, D, A = ()>
+where
+    A: 'a,
+    D: ?Sized + DocAllocator<'a, A>,
+{
+    /// Converts `self` into a document
+    fn pretty(self, allocator: &'a D) -> DocBuilder<'a, D, A>;
+}
+
+impl<'a, D, A> Pretty<'a, D, A> for DocBuilder<'a, D, A>
+where
+    A: 'a,
+    D: ?Sized + DocAllocator<'a, A>,
+{
+    fn pretty(self, _: &'a D) -> DocBuilder<'a, D, A> {
+        self
+    }
+}
+
+impl<'a, D, A> DocBuilder<'a, D, A>
 where
     D: ?Sized + DocAllocator<'a, A>,
 {
@@ -913,7 +1891,7 @@ where
         let that = that.into();
         DocBuilder(
             allocator,
-            Doc::FlatAlt(allocator.alloc_cow(this.into()), allocator.alloc_cow(that)).into(),
+            Doc::FlatAlt(allocator.alloc_cow(this), allocator.alloc_cow(that)).into(),
         )
     }
 
@@ -1041,19 +2019,9 @@ where
     where
         DocBuilder<'a, D, A>: Clone,
     {
-        let spaces = {
-            use crate::render::SPACES;
-            let DocBuilder(allocator, _) = self;
-            let mut doc = allocator.nil();
-            let mut remaining = adjust;
-            while remaining != 0 {
-                let i = SPACES.len().min(remaining);
-                remaining -= i;
-                doc = doc.append(allocator.text(&SPACES[..i]))
-            }
-            doc
-        };
-        spaces.append(self).hang(adjust.try_into().unwrap())
+        spaces(self.0, adjust)
+            .append(self)
+            .hang(adjust.try_into().unwrap())
     }
 
     /// Lays out `self` and provides the column width of it available to `f`
@@ -1087,6 +2055,100 @@ where
         })
     }
 
+    /// Pads `self` with spaces so that it ends at the right margin of the page. If the current
+    /// column is already past that point `self` is placed without any padding.
+    ///
+    /// `self` is expected to fit on a single line.
+    ///
+    /// ```rust
+    /// use pretty::DocAllocator;
+    ///
+    /// let arena = pretty::Arena::<()>::new();
+    /// let doc = arena.text("Title").append(arena.text("v1.0").align_right());
+    /// assert_eq!(doc.1.pretty(12).to_string(), "Title   v1.0");
+    /// ```
+    #[inline]
+    pub fn align_right(self) -> DocBuilder<'a, D, A>
+    where
+        D::Doc: Clone,
+    {
+        self.pad_to(|page_width, width| page_width.saturating_sub(width))
+    }
+
+    /// Pads `self` with spaces so that it is centered on the page. If the current column is
+    /// already past that point `self` is placed without any padding.
+    ///
+    /// `self` is expected to fit on a single line.
+    ///
+    /// ```rust
+    /// use pretty::DocAllocator;
+    ///
+    /// let arena = pretty::Arena::<()>::new();
+    /// let doc = arena.text("Title").center();
+    /// assert_eq!(doc.1.pretty(11).to_string(), "   Title");
+    /// ```
+    #[inline]
+    pub fn center(self) -> DocBuilder<'a, D, A>
+    where
+        D::Doc: Clone,
+    {
+        self.pad_to(|page_width, width| page_width.saturating_sub(width) / 2)
+    }
+
+    /// Replaces every group nested inside more than `depth` other groups with `placeholder`,
+    /// bounding the size of the output for deeply nested documents. Groups which only contain a
+    /// line break, such as `softline`, are left as is.
+    ///
+    /// ```rust
+    /// use pretty::{Arena, DocAllocator, Pretty};
+    ///
+    /// let arena = Arena::<()>::new();
+    /// let value = vec![vec![1, 2], vec![3]];
+    /// let doc = value.pretty(&arena).elide_below(1, "[…]");
+    /// assert_eq!(doc.1.pretty(80).to_string(), "[[…], […]]");
+    /// ```
+    pub fn elide_below<P>(self, depth: usize, placeholder: P) -> DocBuilder<'a, D, A>
+    where
+        P: Into<BuildDoc<'a, D::Doc, A>>,
+        D::Doc: Clone,
+        A: Clone,
+    {
+        let allocator = self.0;
+        let placeholder = DocBuilder(allocator, placeholder.into()).into_doc();
+        let doc = self.into_doc();
+        DocBuilder(
+            allocator,
+            BuildDoc::DocPtr(elide::elide_below(allocator, &doc, depth, &placeholder)),
+        )
+    }
+
+    /// Pads `self` so that it starts at the column returned by `start(page_width, width)`. An
+    /// unlimited page (`usize::MAX`, which is also what `render::measure` lays out with) has no
+    /// margin to pad to, so `self` is not padded.
+    fn pad_to(self, start: fn(usize, usize) -> usize) -> DocBuilder<'a, D, A>
+    where
+        D::Doc: Clone,
+    {
+        let allocator = self.0;
+        let doc = self.into_doc();
+        let width = render::measure(&doc);
+        allocator.page_width(move |page_width| {
+            let doc = doc.clone();
+            allocator
+                .column(move |column| {
+                    let padding = if page_width == usize::MAX {
+                        0
+                    } else {
+                        start(page_width, width).saturating_sub(column)
+                    };
+                    spaces(allocator, padding)
+                        .append(BuildDoc::DocPtr(doc.clone()))
+                        .into_doc()
+                })
+                .into_doc()
+        })
+    }
+
     /// Puts `self` between `before` and `after`
     #[inline]
     pub fn enclose<E, F>(self, before: E, after: F) -> DocBuilder<'a, D, A>
@@ -1137,6 +2199,100 @@ where
     }
 }
 
+/// Controls whether a separator is placed after the last document of a `Delimited` sequence
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TrailingComma {
+    /// Never insert a trailing separator
+    Never,
+    /// Always insert a trailing separator, as in `(a,)`
+    Always,
+    /// Insert a trailing separator only when each document is put on its own line
+    IfBroken,
+}
+
+/// Builder for a sequence of documents between two delimiters. Created by
+/// `DocAllocator::delimited`.
+pub struct Delimited<'a, D, A, S>
+where
+    D: ?Sized + DocAllocator<'a, A>,
+{
+    allocator: &'a D,
+    open: BuildDoc<'a, D::Doc, A>,
+    separator: S,
+    close: BuildDoc<'a, D::Doc, A>,
+    trailing_comma: TrailingComma,
+    nest: isize,
+    padded: bool,
+}
+
+impl<'a, D, A, S> Delimited<'a, D, A, S>
+where
+    D: ?Sized + DocAllocator<'a, A>,
+{
+    /// Sets the indentation of the documents when they are put on their own lines (default: 4).
+    #[inline]
+    pub fn nest(mut self, offset: isize) -> Self {
+        self.nest = offset;
+        self
+    }
+
+    /// Puts a space after `open` and before `close` when laid out on a single line.
+    #[inline]
+    pub fn padded(mut self) -> Self {
+        self.padded = true;
+        self
+    }
+
+    /// Lays out `docs` between the delimiters.
+    ///
+    /// An empty `docs` results in just `open` followed by `close`.
+    pub fn build<I>(self, docs: I) -> DocBuilder<'a, D, A>
+    where
+        I: IntoIterator,
+        I::Item: Into<BuildDoc<'a, D::Doc, A>>,
+        S: Into<BuildDoc<'a, D::Doc, A>> + Clone,
+    {
+        let Delimited {
+            allocator,
+            open,
+            separator,
+            close,
+            trailing_comma,
+            nest,
+            padded,
+        } = self;
+
+        let mut docs = docs.into_iter().peekable();
+        if docs.peek().is_none() {
+            return DocBuilder(allocator, open).append(close);
+        }
+
+        let body = separate_with(allocator, docs, || {
+            DocBuilder(allocator, separator.clone().into()).append(allocator.line())
+        });
+        let body = match trailing_comma {
+            TrailingComma::Never => body,
+            TrailingComma::Always => body.append(separator),
+            TrailingComma::IfBroken => {
+                body.append(DocBuilder(allocator, separator.into()).flat_alt(allocator.nil()))
+            }
+        };
+
+        let line = || {
+            if padded {
+                allocator.line()
+            } else {
+                allocator.line_()
+            }
+        };
+        DocBuilder(allocator, open)
+            .append(line().append(body).nest(nest))
+            .append(line())
+            .append(close)
+            .group()
+    }
+}
+
 /// Newtype wrapper for `&Doc`
 pub struct RefDoc<'a, A = ()>(pub &'a Doc<'a, RefDoc<'a, A>, A>);
 
@@ -1156,11 +2312,70 @@ where
     }
 }
 
+impl<'a, A> PartialEq for RefDoc<'a, A>
+where
+    A: PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        *self.0 == *other.0
+    }
+}
+
+impl<'a, A> Eq for RefDoc<'a, A> where A: Eq {}
+
+impl<'a, A> Hash for RefDoc<'a, A>
+where
+    A: Hash,
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state)
+    }
+}
+
 impl<'a, A> Deref for RefDoc<'a, A> {
     type Target = Doc<'a, RefDoc<'a, A>, A>;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        self.0
+    }
+}
+
+impl<'a, A> RefDoc<'a, A>
+where
+    A: Clone,
+{
+    /// Writes a rendered document to a `RenderAnnotated<A>` writer, reusing the layout of the
+    /// groups which were already rendered with `cache` wherever they start at the same column and
+    /// indentation. Documents which are edited between renders should share their unchanged parts.
+    ///
+    /// ```
+    /// use pretty::{Arena, DocAllocator, FmtWrite, LayoutCache};
+    /// let arena = Arena::<()>::new();
+    /// let mut cache = LayoutCache::new();
+    /// let item = arena.text("a").append(arena.line()).append("b").group().into_doc();
+    ///
+    /// let mut s = String::new();
+    /// let doc = arena.nil().append(item).into_doc();
+    /// doc.render_incremental(80, &mut cache, &mut FmtWrite::new(&mut s)).unwrap();
+    /// assert_eq!(s, "a b");
+    ///
+    /// // Only the new group is laid out
+    /// let mut s = String::new();
+    /// let doc = arena.nil().append(item).append(arena.hardline()).append(arena.text("c").group()).into_doc();
+    /// doc.render_incremental(80, &mut cache, &mut FmtWrite::new(&mut s)).unwrap();
+    /// assert_eq!(s, "a b\nc");
+    /// ```
+    #[inline]
+    pub fn render_incremental<W>(
+        &self,
+        width: usize,
+        cache: &mut LayoutCache<'a, A>,
+        out: &mut W,
+    ) -> Result<(), W::Error>
+    where
+        W: ?Sized + RenderAnnotated<A>,
+    {
+        incremental::render(*self, width, cache, out)
     }
 }
 
@@ -1274,8 +2489,6 @@ impl<'a, A> DocAllocator<'a, A> for Arena<'a, A> {
 
 #[cfg(test)]
 mod tests {
-    use difference;
-
     use super::*;
 
     #[cfg(target_pointer_width = "64")]
@@ -1463,6 +2676,520 @@ mod tests {
     }
 
     #[test]
+    fn separators() {
+        let docs = || vec!["aaa", "bbb", "ccc"];
+
+        test!(BoxDoc::<()>::hsep(docs()), "aaa bbb ccc");
+        test!(5, BoxDoc::<()>::hsep(docs()), "aaa bbb ccc");
+
+        test!(RcDoc::<()>::vsep(docs()), "aaa\nbbb\nccc");
+        test!(RcDoc::<()>::vsep(docs()).group(), "aaa bbb ccc");
+
+        test!(BoxDoc::<()>::sep(docs()), "aaa bbb ccc");
+        test!(11, BoxDoc::<()>::sep(docs()), "aaa bbb ccc");
+        test!(10, BoxDoc::<()>::sep(docs()), "aaa\nbbb\nccc");
+
+        test!(5, RcDoc::<()>::hcat(docs()), "aaabbbccc");
+
+        test!(RcDoc::<()>::vcat(docs()), "aaa\nbbb\nccc");
+        test!(RcDoc::<()>::vcat(docs()).group(), "aaabbbccc");
+
+        test!(BoxDoc::<()>::cat(docs()), "aaabbbccc");
+        test!(9, BoxDoc::<()>::cat(docs()), "aaabbbccc");
+        test!(8, BoxDoc::<()>::cat(docs()), "aaa\nbbb\nccc");
+
+        test!(BoxDoc::<()>::sep(Vec::<BoxDoc<()>>::new()), "");
+        test!(BoxDoc::<()>::vsep(vec!["a"]), "a");
+    }
+
+    #[test]
+    fn punctuate() {
+        let arena = Arena::<()>::new();
+        let items = arena.punctuate(vec!["aaa", "bbb", "ccc"], ",");
+        let doc = arena
+            .text("[")
+            .append(arena.sep(items).nest(1))
+            .append("]")
+            .group();
+
+        test!(doc.1, "[aaa, bbb, ccc]");
+        test!(15, doc.1, "[aaa, bbb, ccc]");
+        test!(14, doc.1, "[aaa,\n bbb,\n ccc]");
+
+        let docs = RcDoc::<()>::punctuate(vec!["a", "b"], RcDoc::text(";"));
+        test!(RcDoc::hsep(docs), "a; b");
+        assert!(RcDoc::<()>::punctuate(Vec::<RcDoc<()>>::new(), ",").is_empty());
+    }
+
+    #[test]
+    fn delimited() {
+        let arena = Arena::<()>::new();
+
+        let doc = arena.tupled(vec!["aaa", "bbb"]);
+        test!(doc.1, "(aaa, bbb)");
+        test!(10, doc.1, "(aaa, bbb)");
+        test!(9, doc.1, "(\n    aaa,\n    bbb\n)");
+
+        test!(arena.list(Vec::<RefDoc<()>>::new()).1, "[]");
+        test!(1, arena.list(vec!["a"]).1, "[\n    a\n]");
+
+        let doc = |trailing_comma| {
+            arena
+                .delimited("(", ",", ")", trailing_comma)
+                .nest(2)
+                .build(vec!["a"])
+        };
+        test!(doc(TrailingComma::Never).1, "(a)");
+        test!(1, doc(TrailingComma::Never).1, "(\n  a\n)");
+        test!(doc(TrailingComma::Always).1, "(a,)");
+        test!(1, doc(TrailingComma::Always).1, "(\n  a,\n)");
+        test!(doc(TrailingComma::IfBroken).1, "(a)");
+        test!(1, doc(TrailingComma::IfBroken).1, "(\n  a,\n)");
+
+        let doc = arena
+            .delimited("{", ";", "}", TrailingComma::IfBroken)
+            .padded()
+            .build(vec![arena.text("x"), arena.list(vec!["1", "2"])]);
+        test!(doc.1, "{ x; [1, 2] }");
+        test!(12, doc.1, "{\n    x;\n    [1, 2];\n}");
+    }
+
+    #[test]
+    fn align_right_and_center() {
+        let arena = Arena::<()>::new();
+        let doc = arena
+            .text("fn main()")
+            .append(arena.text("1").align_right())
+            .append(arena.hardline())
+            .append(arena.text("~").center());
+        test!(12, doc.1, "fn main()  1\n     ~");
+
+        // Text which is already past the margin is left as is
+        let doc = arena
+            .text("a long line")
+            .append(arena.text("1").align_right());
+        test!(5, doc.1, "a long line1");
+
+        // Padded documents are measured without padding
+        let doc = arena.text("x").align_right().center();
+        test!(11, doc.1, "          x");
+        let doc = arena.text("x").center().align_right();
+        test!(11, doc.1, "          x");
+        test!(usize::MAX, doc.1, "x");
+    }
+
+    #[test]
+    fn page_width_fitting() {
+        let arena = Arena::<()>::new();
+        // The right aligned text ends at the margin, so the group fits exactly
+        let doc = arena
+            .text("let x =")
+            .append(arena.line().append(arena.text("1").align_right()))
+            .group();
+        test!(10, doc.1, "let x =  1");
+
+        let doc = arena
+            .text("[")
+            .append(arena.line_())
+            .append(arena.page_width(|w| arena.as_string(w).into_doc()))
+            .append("]")
+            .group();
+        test!(4, doc.1, "[4]");
+        test!(2, doc.1, "[\n2]");
+    }
+
+    #[test]
+    fn lazy_is_built_once() {
+        let arena = Arena::<()>::new();
+        let calls = Cell::new(0);
+        let doc = arena
+            .text("a")
+            .append(arena.line())
+            .append(arena.lazy(|| {
+                calls.set(calls.get() + 1);
+                arena.text("b").append(arena.line()).append("c").group()
+            }))
+            .group();
+        test!(80, doc.1, "a b c");
+        test!(4, doc.1, "a\nb c");
+        test!(2, doc.1, "a\nb\nc");
+        assert_eq!(calls.get(), 1);
+        // Lazy documents are told apart from `page_width` without building them
+        assert!(doc.1.dump().contains("Lazy("));
+        assert!(!doc.1.dump().contains("PageWidth("));
+        assert_eq!(calls.get(), 1);
+
+        // Documents past the output limit are never built
+        calls.set(0);
+        let calls = &calls;
+        let doc = arena.intersperse(
+            (0..100).map(|i| {
+                arena.lazy(move || {
+                    calls.set(calls.get() + 1);
+                    i.to_string()
+                })
+            }),
+            arena.hardline(),
+        );
+        let mut s = String::new();
+        doc.1
+            .render_with_options(
+                &RenderOptions::new(80).max_lines(3),
+                &mut FmtWrite::new(&mut s),
+            )
+            .unwrap();
+        assert_eq!(s, "0\n1\n2\n…");
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[test]
+    fn render_string_and_vec() {
+        let arena = Arena::<()>::new();
+        let doc = arena
+            .text("let")
+            .append(arena.line())
+            .append(arena.text("åäö").append(arena.line()).append("=").group())
+            .append(arena.hardline().append("1").nest(8))
+            .group();
+
+        for width in [0, 10, 80] {
+            let expected = doc.1.pretty(width).to_string();
+            let s = doc.1.render_string(width);
+            assert_eq!(s, expected);
+            assert!(s.capacity() >= "letåäö=1".len());
+            assert_eq!(doc.1.render_vec(width), expected.into_bytes());
+        }
+    }
+
+    #[test]
+    fn structural_equality() {
+        use std::collections::HashSet;
+
+        let arena = Arena::<()>::new();
+        let doc = |name: String| {
+            arena
+                .text("fn ")
+                .append(name)
+                .append(arena.line())
+                .append("{}")
+                .nest(4)
+                .group()
+                .into_doc()
+        };
+        let column = arena.column(|_| arena.nil().into_doc()).into_doc();
+
+        // Owned and borrowed text is compared by content
+        assert_eq!(doc("main".into()), doc("main".into()));
+        assert_eq!(
+            arena.text("a").into_doc(),
+            arena.text(String::from("a")).into_doc()
+        );
+        assert_ne!(doc("main".into()), doc("other".into()));
+        assert_ne!(
+            arena.text("a").nest(1).into_doc(),
+            arena.text("a").nest(2).into_doc()
+        );
+        // Closures are compared by address
+        assert_eq!(column, column.clone());
+        assert_ne!(column, arena.column(|_| arena.nil().into_doc()).into_doc());
+
+        let unique: HashSet<_> = vec![
+            doc("a".into()),
+            doc("b".into()),
+            doc("a".into()),
+            column,
+            column,
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(unique.len(), 3);
+
+        // Long sequences do not overflow the stack
+        let long = || {
+            (0..100_000)
+                .fold(arena.nil(), |doc, _| doc.append("x"))
+                .into_doc()
+        };
+        assert_eq!(long(), long());
+        assert!(!format!("{:?}", column).contains(".."));
+    }
+
+    /// The depth of the deepest document in `doc`
+    fn depth<'a, T, A>(doc: &Doc<'a, T, A>) -> usize
+    where
+        T: DocPtr<'a, A>,
+    {
+        let mut max = 0;
+        let mut stack = vec![(doc, 1)];
+        while let Some((doc, depth)) = stack.pop() {
+            max = max.max(depth);
+            match *doc {
+                Doc::Append(ref l, ref r)
+                | Doc::FlatAlt(ref l, ref r)
+                | Doc::Union(ref l, ref r) => {
+                    stack.push((l, depth + 1));
+                    stack.push((r, depth + 1));
+                }
+                Doc::Group(ref doc) | Doc::Nest(_, ref doc) | Doc::Annotated(_, ref doc) => {
+                    stack.push((doc, depth + 1))
+                }
+                _ => {}
+            }
+        }
+        max
+    }
+
+    #[test]
+    fn long_documents() {
+        let doc = BoxDoc::<()>::concat((0..1_000_000).map(|_| BoxDoc::text("x")));
+        assert_eq!(depth(&doc), 21);
+        assert_eq!(doc.render_string(80).len(), 1_000_000);
+
+        let doc = RcDoc::<()>::intersperse((0..100_000).map(|_| "x"), RcDoc::line());
+        assert!(depth(&doc) < 40);
+        assert_eq!(doc.group().render_string(10).lines().count(), 100_000);
+
+        // Deep documents are dropped without overflowing the stack
+        let doc = (0..1_000_000).fold(BoxDoc::<()>::nil(), |doc, _| doc.append("x"));
+        assert_eq!(depth(&doc), 1_000_000);
+        drop(doc);
+        let doc = (0..1_000_000).fold(RcDoc::<()>::text("x"), |doc, _| doc.nest(1).group());
+        drop(doc);
+    }
+
+    #[test]
+    fn render_options() {
+        let arena = Arena::<()>::new();
+        let doc = arena.text("list:").append(
+            arena
+                .hardline()
+                .append(arena.intersperse(vec!["äbcdef", "x", "yz"], arena.hardline()))
+                .nest(2),
+        );
+        let render = |options: RenderOptions| {
+            let mut s = String::new();
+            doc.1
+                .render_with_options(&options, &mut FmtWrite::new(&mut s))
+                .unwrap();
+            s
+        };
+
+        assert_eq!(render(RenderOptions::new(80)), "list:\n  äbcdef\n  x\n  yz");
+        assert_eq!(
+            render(RenderOptions::new(80).max_lines(2)),
+            "list:\n  äbcdef\n…"
+        );
+        assert_eq!(
+            render(RenderOptions::new(80).max_lines(4)),
+            "list:\n  äbcdef\n  x\n  yz"
+        );
+        assert_eq!(
+            render(RenderOptions::new(80).max_line_chars(4)),
+            "list…\n  äb…\n  x\n  yz"
+        );
+        assert_eq!(
+            render(RenderOptions::new(80).max_lines(0).max_line_chars(0)),
+            "…\n…"
+        );
+    }
+
+    #[test]
+    fn render_line_range() {
+        struct CountWrites(String, usize);
+
+        impl Render for CountWrites {
+            type Error = fmt::Error;
+
+            fn write_str(&mut self, s: &str) -> Result<usize, fmt::Error> {
+                self.0.push_str(s);
+                self.1 += 1;
+                Ok(s.len())
+            }
+
+            fn write_zero_error(&self) -> fmt::Error {
+                fmt::Error
+            }
+        }
+
+        impl RenderAnnotated<()> for CountWrites {
+            fn push_annotation(&mut self, _: &()) -> Result<(), fmt::Error> {
+                Ok(())
+            }
+
+            fn pop_annotation(&mut self) -> Result<(), fmt::Error> {
+                Ok(())
+            }
+        }
+
+        let arena = Arena::<()>::new();
+        let doc = arena.text("items:").append(
+            arena
+                .hardline()
+                .append(arena.intersperse((0..100).map(|i| i.to_string()), arena.hardline()))
+                .nest(2),
+        );
+        assert_eq!(doc.1.line_count(80), 101);
+
+        let render = |options: RenderOptions| {
+            let mut out = CountWrites(String::new(), 0);
+            let stats = doc.1.render_with_options(&options, &mut out).unwrap();
+            (out.0, out.1, stats.truncated)
+        };
+        assert_eq!(
+            render(RenderOptions::new(80).lines(50..53)),
+            ("  49\n  50\n  51".to_string(), 8, true)
+        );
+        assert_eq!(
+            render(RenderOptions::new(80).lines(0..2)),
+            ("items:\n  0".to_string(), 4, true)
+        );
+        assert_eq!(
+            render(RenderOptions::new(80).lines(99..200)),
+            ("  98\n  99".to_string(), 5, false)
+        );
+        assert_eq!(
+            render(RenderOptions::new(80).lines(10..20).max_lines(1)),
+            ("  9\n…".to_string(), 3, true)
+        );
+        assert_eq!(
+            render(RenderOptions::new(80).lines(3..3)),
+            (String::new(), 0, true)
+        );
+
+        // The whole document is counted when it is not truncated
+        let stats = doc
+            .1
+            .render_with_options(
+                &RenderOptions::new(80).lines(99..200),
+                &mut CountWrites(String::new(), 0),
+            )
+            .unwrap();
+        assert_eq!(stats.lines, 101);
+    }
+
+    #[test]
+    fn render_line_range_annotations() {
+        /// Writes the annotations as brackets
+        struct Brackets(String);
+
+        impl Render for Brackets {
+            type Error = fmt::Error;
+
+            fn write_str(&mut self, s: &str) -> Result<usize, fmt::Error> {
+                self.0.push_str(s);
+                Ok(s.len())
+            }
+
+            fn write_zero_error(&self) -> fmt::Error {
+                fmt::Error
+            }
+        }
+
+        impl RenderAnnotated<usize> for Brackets {
+            fn push_annotation(&mut self, ann: &usize) -> Result<(), fmt::Error> {
+                self.0.push_str(&format!("[{}", ann));
+                Ok(())
+            }
+
+            fn pop_annotation(&mut self) -> Result<(), fmt::Error> {
+                self.0.push(']');
+                Ok(())
+            }
+        }
+
+        let arena = Arena::<usize>::new();
+        let lines = || {
+            arena.intersperse(
+                (0..6).map(|i| arena.as_string(i).annotate(i)),
+                arena.hardline(),
+            )
+        };
+        fn render<'a>(
+            doc: DocBuilder<'a, Arena<'a, usize>, usize>,
+            options: RenderOptions,
+        ) -> String {
+            let mut out = Brackets(String::new());
+            doc.1.render_with_options(&options, &mut out).unwrap();
+            out.0
+        }
+
+        // Nothing is written for the annotations of the other lines
+        assert_eq!(render(lines(), RenderOptions::new(80).lines(4..5)), "[44]");
+        assert_eq!(
+            render(lines(), RenderOptions::new(80).lines(1..3)),
+            "[11]\n[22]"
+        );
+        // Annotations which are open when the range starts are written before its first text and
+        // closed when the output ends
+        assert_eq!(
+            render(lines().annotate(9), RenderOptions::new(80).lines(4..5)),
+            "[9[44]]"
+        );
+        assert_eq!(
+            render(lines().annotate(9), RenderOptions::new(80).max_lines(1)),
+            "[9[00]\n…]"
+        );
+        assert_eq!(
+            render(lines().annotate(9), RenderOptions::new(80).lines(6..7)),
+            ""
+        );
+    }
+
+    #[test]
+    fn render_stats() {
+        let arena = Arena::<()>::new();
+        let doc = arena
+            .text("fn f(")
+            .append(arena.line_().append(arena.text("a_long_argument")).nest(4))
+            .append(arena.line_())
+            .append(")")
+            .group();
+
+        let render = |width| {
+            doc.1
+                .render_with_options(
+                    &RenderOptions::new(width),
+                    &mut FmtWrite::new(String::new()),
+                )
+                .unwrap()
+        };
+        assert_eq!(
+            render(80),
+            RenderStats {
+                lines: 1,
+                max_width: 21,
+                overflows: vec![],
+                truncated: false,
+            }
+        );
+        assert_eq!(
+            render(10),
+            RenderStats {
+                lines: 3,
+                max_width: 19,
+                overflows: vec![Overflow { line: 1, width: 19 }],
+                truncated: false,
+            }
+        );
+
+        let stats = arena
+            .nil()
+            .1
+            .render_with_options(&RenderOptions::new(0), &mut FmtWrite::new(String::new()))
+            .unwrap();
+        assert_eq!(
+            stats,
+            RenderStats {
+                lines: 1,
+                max_width: 0,
+                overflows: vec![],
+                truncated: false,
+            }
+        );
+    }
+
+    #[test]
+    #[allow(clippy::legacy_numeric_constants)]
     fn usize_max_value() {
         let doc: BoxDoc<()> = BoxDoc::group(
             BoxDoc::text("test")
@@ -1496,6 +3223,10 @@ mod tests {
         fn write_str_all(&mut self, s: &str) -> Result<(), W::Error> {
             self.upstream.write_str_all(s)
         }
+
+        fn write_zero_error(&self) -> W::Error {
+            self.upstream.write_zero_error()
+        }
     }
 
     impl<W> RenderAnnotated<()> for TestWriter<W>
@@ -1,6 +1,8 @@
+use std::borrow::Cow;
 use std::cmp;
 use std::fmt;
 use std::io;
+use std::ops::Range;
 #[cfg(feature = "termcolor")]
 use termcolor::{ColorSpec, WriteColor};
 
@@ -10,15 +12,53 @@ use crate::{Doc, DocPtr};
 pub trait Render {
     type Error;
 
+    /// Writes a prefix of `s`, returning its length in bytes. The prefix may be shorter than `s`
+    /// but must end at a `char` boundary. Writing nothing (`Ok(0)`) when `s` is not empty means
+    /// that no more output can be written, which `write_str_all` reports as
+    /// `write_zero_error`.
     fn write_str(&mut self, s: &str) -> Result<usize, Self::Error>;
 
+    /// Writes all of `s`, calling `write_str` until everything is written
     fn write_str_all(&mut self, mut s: &str) -> Result<(), Self::Error> {
         while !s.is_empty() {
             let count = self.write_str(s)?;
+            if count == 0 {
+                return Err(self.write_zero_error());
+            }
             s = &s[count..];
         }
         Ok(())
     }
+
+    /// Returns the error to report when `write_str` writes nothing. There is no default as no
+    /// error can be created for an arbitrary `Self::Error`, and a writer which stops accepting
+    /// output must be reported as an error rather than with a panic or an endless loop.
+    fn write_zero_error(&self) -> Self::Error;
+}
+
+/// Writes a prefix of `s` to `upstream` following the contract of `Render::write_str`, completing
+/// the last character if the write ended in the middle of it
+fn write_io<W>(upstream: &mut W, s: &str) -> io::Result<usize>
+where
+    W: ?Sized + io::Write,
+{
+    if s.is_empty() {
+        return Ok(0);
+    }
+    let count = loop {
+        match upstream.write(s.as_bytes()) {
+            Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+            Ok(count) => break count,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => (),
+            Err(err) => return Err(err),
+        }
+    };
+    let mut end = count;
+    while !s.is_char_boundary(end) {
+        end += 1;
+    }
+    upstream.write_all(&s.as_bytes()[count..end])?;
+    Ok(end)
 }
 
 /// Writes to something implementing `std::io::Write`
@@ -39,12 +79,16 @@ where
     type Error = io::Error;
 
     fn write_str(&mut self, s: &str) -> io::Result<usize> {
-        self.upstream.write(s.as_bytes())
+        write_io(&mut self.upstream, s)
     }
 
     fn write_str_all(&mut self, s: &str) -> io::Result<()> {
         self.upstream.write_all(s.as_bytes())
     }
+
+    fn write_zero_error(&self) -> io::Error {
+        io::ErrorKind::WriteZero.into()
+    }
 }
 
 /// Writes to something implementing `std::fmt::Write`
@@ -71,6 +115,10 @@ where
     fn write_str_all(&mut self, s: &str) -> fmt::Result {
         self.upstream.write_str(s)
     }
+
+    fn write_zero_error(&self) -> fmt::Error {
+        fmt::Error
+    }
 }
 
 /// Trait representing the operations necessary to write an annotated document.
@@ -105,6 +153,192 @@ where
     }
 }
 
+/// Writes the same output to two renderers
+pub struct Tee<L, R> {
+    left: L,
+    right: R,
+}
+
+impl<L, R> Tee<L, R> {
+    pub fn new(left: L, right: R) -> Tee<L, R> {
+        Tee { left, right }
+    }
+
+    pub fn into_inner(self) -> (L, R) {
+        (self.left, self.right)
+    }
+}
+
+impl<L, R> Render for Tee<L, R>
+where
+    L: Render,
+    R: Render<Error = L::Error>,
+{
+    type Error = L::Error;
+
+    fn write_str(&mut self, s: &str) -> Result<usize, Self::Error> {
+        self.write_str_all(s).map(|_| s.len())
+    }
+
+    fn write_str_all(&mut self, s: &str) -> Result<(), Self::Error> {
+        self.left.write_str_all(s)?;
+        self.right.write_str_all(s)
+    }
+
+    fn write_zero_error(&self) -> Self::Error {
+        self.left.write_zero_error()
+    }
+}
+
+impl<A, L, R> RenderAnnotated<A> for Tee<L, R>
+where
+    L: RenderAnnotated<A>,
+    R: RenderAnnotated<A, Error = L::Error>,
+{
+    fn push_annotation(&mut self, annotation: &A) -> Result<(), Self::Error> {
+        self.left.push_annotation(annotation)?;
+        self.right.push_annotation(annotation)
+    }
+
+    fn pop_annotation(&mut self) -> Result<(), Self::Error> {
+        self.left.pop_annotation()?;
+        self.right.pop_annotation()
+    }
+}
+
+/// Counts the bytes and lines written to the wrapped renderer
+pub struct Counting<W> {
+    upstream: W,
+    bytes: usize,
+    lines: usize,
+    column: usize,
+}
+
+impl<W> Counting<W> {
+    pub fn new(upstream: W) -> Counting<W> {
+        Counting {
+            upstream,
+            bytes: 0,
+            lines: 1,
+            column: 0,
+        }
+    }
+
+    /// Returns the number of bytes written
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Returns the number of lines written, counting the current line even if it is empty
+    pub fn lines(&self) -> usize {
+        self.lines
+    }
+
+    /// Returns the number of bytes written since the last newline, which is the column used by
+    /// the layout
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    pub fn into_inner(self) -> W {
+        self.upstream
+    }
+}
+
+impl<W> Render for Counting<W>
+where
+    W: Render,
+{
+    type Error = W::Error;
+
+    fn write_str(&mut self, s: &str) -> Result<usize, W::Error> {
+        let count = self.upstream.write_str(s)?;
+        let written = &s[..count];
+        self.bytes += count;
+        match written.rfind('\n') {
+            Some(i) => {
+                self.lines += written.matches('\n').count();
+                self.column = count - i - 1;
+            }
+            None => self.column += count,
+        }
+        Ok(count)
+    }
+
+    fn write_zero_error(&self) -> W::Error {
+        self.upstream.write_zero_error()
+    }
+}
+
+impl<A, W> RenderAnnotated<A> for Counting<W>
+where
+    W: RenderAnnotated<A>,
+{
+    fn push_annotation(&mut self, annotation: &A) -> Result<(), Self::Error> {
+        self.upstream.push_annotation(annotation)
+    }
+
+    fn pop_annotation(&mut self) -> Result<(), Self::Error> {
+        self.upstream.pop_annotation()
+    }
+}
+
+/// Transforms the text written to the wrapped renderer, for instance to escape it. The
+/// transformation is applied to all of the output, including the newlines and the indentation
+/// written by the renderer, which may be split into several calls.
+pub struct MapText<W, F> {
+    upstream: W,
+    f: F,
+}
+
+impl<W, F> MapText<W, F>
+where
+    F: FnMut(&str) -> Cow<'_, str>,
+{
+    pub fn new(upstream: W, f: F) -> MapText<W, F> {
+        MapText { upstream, f }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.upstream
+    }
+}
+
+impl<W, F> Render for MapText<W, F>
+where
+    W: Render,
+    F: FnMut(&str) -> Cow<'_, str>,
+{
+    type Error = W::Error;
+
+    fn write_str(&mut self, s: &str) -> Result<usize, W::Error> {
+        self.write_str_all(s).map(|_| s.len())
+    }
+
+    fn write_str_all(&mut self, s: &str) -> Result<(), W::Error> {
+        let mapped = (self.f)(s);
+        self.upstream.write_str_all(&mapped)
+    }
+
+    fn write_zero_error(&self) -> W::Error {
+        self.upstream.write_zero_error()
+    }
+}
+
+impl<A, W, F> RenderAnnotated<A> for MapText<W, F>
+where
+    W: RenderAnnotated<A>,
+    F: FnMut(&str) -> Cow<'_, str>,
+{
+    fn push_annotation(&mut self, annotation: &A) -> Result<(), Self::Error> {
+        self.upstream.push_annotation(annotation)
+    }
+
+    fn pop_annotation(&mut self) -> Result<(), Self::Error> {
+        self.upstream.pop_annotation()
+    }
+}
+
 #[cfg(feature = "termcolor")]
 pub struct TermColored<W> {
     color_stack: Vec<ColorSpec>,
@@ -129,12 +363,16 @@ where
     type Error = io::Error;
 
     fn write_str(&mut self, s: &str) -> io::Result<usize> {
-        self.upstream.write(s.as_bytes())
+        write_io(&mut self.upstream, s)
     }
 
     fn write_str_all(&mut self, s: &str) -> io::Result<()> {
         self.upstream.write_all(s.as_bytes())
     }
+
+    fn write_zero_error(&self) -> io::Error {
+        io::ErrorKind::WriteZero.into()
+    }
 }
 
 #[cfg(feature = "termcolor")]
@@ -163,248 +401,1321 @@ macro_rules! make_spaces {
 
 pub(crate) const SPACES: &str = make_spaces!(,,,,,,,,,,);
 
-#[inline]
-pub fn best<'a, W, T, A>(doc: &Doc<'a, T, A>, width: usize, out: &mut W) -> Result<(), W::Error>
+/// Options controlling how a document is rendered. See `Doc::render_with_options`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RenderOptions {
+    width: usize,
+    max_lines: Option<usize>,
+    max_line_chars: Option<usize>,
+    lines: Range<usize>,
+}
+
+impl RenderOptions {
+    /// Renders documents to fit in `width` columns, without limiting the output.
+    pub fn new(width: usize) -> Self {
+        RenderOptions {
+            width,
+            max_lines: None,
+            max_line_chars: None,
+            lines: 0..usize::MAX,
+        }
+    }
+
+    /// Stops rendering after `max_lines` lines. If there is more output a final line with `…` is
+    /// added. The first line is always rendered.
+    pub fn max_lines(mut self, max_lines: usize) -> Self {
+        self.max_lines = Some(max_lines);
+        self
+    }
+
+    /// Cuts lines after `max_line_chars` characters, replacing the rest of the line with `…`.
+    pub fn max_line_chars(mut self, max_line_chars: usize) -> Self {
+        self.max_line_chars = Some(max_line_chars);
+        self
+    }
+
+    /// Only writes the lines in the zero-based range `lines`. The whole document is still laid out
+    /// up to the end of the range, but nothing is written for the lines before it and rendering
+    /// stops after it. `max_lines` counts the lines from the start of the range.
+    pub fn lines(mut self, lines: Range<usize>) -> Self {
+        self.lines = lines;
+        self
+    }
+}
+
+/// Statistics about the layout of a rendered document. See `Doc::render_with_options`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct RenderStats {
+    /// The number of lines laid out. When rendering stops early (see `truncated`) the lines after
+    /// that point are not laid out and are not counted.
+    pub lines: usize,
+    /// The width of the widest line laid out
+    pub max_width: usize,
+    /// The lines laid out that are wider than the width the document was rendered with
+    pub overflows: Vec<Overflow>,
+    /// Whether rendering stopped before the end of the document, at the end of
+    /// `RenderOptions::lines` or after `RenderOptions::max_lines`
+    pub truncated: bool,
+}
+
+/// A line that did not fit in the width it was rendered with
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Overflow {
+    /// The zero-based index of the line
+    pub line: usize,
+    /// The width of the line
+    pub width: usize,
+}
+
+impl RenderStats {
+    fn end_line(&mut self, line_width: usize, width: usize) {
+        if line_width > width {
+            self.overflows.push(Overflow {
+                line: self.lines,
+                width: line_width,
+            });
+        }
+        self.max_width = self.max_width.max(line_width);
+        self.lines += 1;
+    }
+}
+
+/// The kind of document a `Decision` is made for
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum DecisionKind {
+    Group,
+    Union,
+}
+
+/// The layout chosen by a `Decision`
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Choice {
+    /// The group is laid out on a single line
+    Flat,
+    /// The group is laid out with its line breaks
+    Break,
+    /// The left document of the union is used
+    Left,
+    /// The right document of the union is used
+    Right,
+}
+
+/// A layout decision made by the renderer for a `group` (outside of a group already laid out
+/// flat) or a `union`. See `Doc::render_traced`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Decision {
+    pub kind: DecisionKind,
+    pub choice: Choice,
+    /// The zero-based line the document starts on
+    pub line: usize,
+    /// The column the document starts at
+    pub column: usize,
+    /// The indentation of the document
+    pub indent: usize,
+    /// The number of columns left on the line
+    pub remaining: usize,
+    /// The column reached when the group or the left side of the union (with the rest of the
+    /// line) was found not to fit, either because it went past the width or because it contains
+    /// a line break which cannot be laid out flat
+    pub failed_at: Option<usize>,
+}
+
+impl Decision {
+    fn new(
+        kind: DecisionKind,
+        fits: Result<(), usize>,
+        line: usize,
+        column: usize,
+        indent: usize,
+        width: usize,
+    ) -> Self {
+        let choice = match (kind, fits) {
+            (DecisionKind::Group, Ok(())) => Choice::Flat,
+            (DecisionKind::Group, Err(_)) => Choice::Break,
+            (DecisionKind::Union, Ok(())) => Choice::Left,
+            (DecisionKind::Union, Err(_)) => Choice::Right,
+        };
+        Decision {
+            kind,
+            choice,
+            line,
+            column,
+            indent,
+            remaining: width.saturating_sub(column),
+            failed_at: fits.err(),
+        }
+    }
+}
+
+/// Writes the output of the renderer, applying the limits of `RenderOptions`
+pub(crate) struct Output<'o, 'd, W: ?Sized, A> {
+    pub(crate) out: &'o mut W,
+    max_lines: Option<usize>,
+    max_line_chars: Option<usize>,
+    lines: Range<usize>,
+    /// The zero-based index of the current line
+    line: usize,
+    line_chars: usize,
+    /// Set once the end of the output is reached, nothing more is written after that
+    done: bool,
+    /// The annotations which are open
+    annotations: Vec<&'d A>,
+    /// The number of open annotations which have been pushed to `out`. Annotations opened before
+    /// the visible lines are only pushed once something is written.
+    written_annotations: usize,
+    /// Whether each open annotation replayed from a `GroupCache` has been pushed to `out`
+    replayed_annotations: Vec<bool>,
+}
+
+impl<'o, 'd, W, A> Output<'o, 'd, W, A>
 where
-    T: DocPtr<'a, A> + 'a,
     W: ?Sized + RenderAnnotated<A>,
 {
-    #[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
-    enum Mode {
-        Break,
-        Flat,
+    pub(crate) fn new(out: &'o mut W, options: &RenderOptions) -> Self {
+        Output {
+            out,
+            max_lines: options.max_lines,
+            max_line_chars: options.max_line_chars,
+            lines: options.lines.clone(),
+            line: 0,
+            line_chars: 0,
+            done: options.lines.is_empty(),
+            annotations: Vec::new(),
+            written_annotations: 0,
+            replayed_annotations: Vec::new(),
+        }
     }
 
-    type Cmd<'d, 'a, T, A> = (usize, Mode, &'d Doc<'a, T, A>);
+    fn visible(&self) -> bool {
+        !self.done && self.line >= self.lines.start
+    }
 
-    fn write_newline<W>(ind: usize, out: &mut W) -> Result<(), W::Error>
-    where
-        W: ?Sized + Render,
-    {
-        out.write_str_all("\n")?;
-        write_spaces(ind, out)
+    /// Pushes the open annotations which have not been written yet
+    fn write_annotations(&mut self) -> Result<(), W::Error> {
+        while let Some(&ann) = self.annotations.get(self.written_annotations) {
+            self.out.push_annotation(ann)?;
+            self.written_annotations += 1;
+        }
+        Ok(())
     }
 
-    fn write_spaces<W>(spaces: usize, out: &mut W) -> Result<(), W::Error>
-    where
-        W: ?Sized + Render,
-    {
+    fn push_annotation(&mut self, ann: &'d A) -> Result<(), W::Error> {
+        self.annotations.push(ann);
+        if self.visible() {
+            self.write_annotations()?;
+        }
+        Ok(())
+    }
+
+    fn pop_annotation(&mut self) -> Result<(), W::Error> {
+        self.annotations.pop();
+        if self.written_annotations > self.annotations.len() {
+            self.written_annotations -= 1;
+            self.out.pop_annotation()?;
+        }
+        Ok(())
+    }
+
+    /// Pushes an annotation replayed from a `GroupCache`. The annotation does not live as long as
+    /// the document so it is only written if the output is visible. Replayed output is balanced,
+    /// so the annotation is popped before any annotation pushed with `push_annotation`.
+    fn push_replayed_annotation(&mut self, ann: &A) -> Result<(), W::Error> {
+        let visible = self.visible();
+        if visible {
+            self.write_annotations()?;
+            self.out.push_annotation(ann)?;
+        }
+        self.replayed_annotations.push(visible);
+        Ok(())
+    }
+
+    fn pop_replayed_annotation(&mut self) -> Result<(), W::Error> {
+        if self.replayed_annotations.pop() == Some(true) {
+            self.out.pop_annotation()?;
+        }
+        Ok(())
+    }
+
+    /// Closes the annotations that were left open if the output was cut short
+    fn close_annotations(&mut self) -> Result<(), W::Error> {
+        while !self.replayed_annotations.is_empty() {
+            self.pop_replayed_annotation()?;
+        }
+        while !self.annotations.is_empty() {
+            self.pop_annotation()?;
+        }
+        Ok(())
+    }
+
+    fn write_text(&mut self, s: &str) -> Result<(), W::Error> {
+        if !self.visible() {
+            return Ok(());
+        }
+        self.write_annotations()?;
+        if let Some(max) = self.max_line_chars {
+            if self.line_chars > max {
+                // The line has already been cut
+                return Ok(());
+            }
+            match s.char_indices().nth(max - self.line_chars) {
+                Some((i, _)) => {
+                    self.line_chars = max + 1;
+                    self.out.write_str_all(&s[..i])?;
+                    return self.out.write_str_all("…");
+                }
+                None => self.line_chars += s.chars().count(),
+            }
+        }
+        self.out.write_str_all(s)
+    }
+
+    fn write_newline(&mut self, ind: usize) -> Result<(), W::Error> {
+        if self.done {
+            return Ok(());
+        }
+        let visible = self.line >= self.lines.start;
+        self.line += 1;
+        self.line_chars = 0;
+        if self.line >= self.lines.end {
+            self.done = true;
+            return Ok(());
+        }
+        if visible {
+            let written = self.line - self.lines.start;
+            if self.max_lines.is_some_and(|max| written >= max) {
+                self.done = true;
+                return self.out.write_str_all("\n…");
+            }
+            self.out.write_str_all("\n")?;
+        }
+        self.write_spaces(ind)
+    }
+
+    fn write_spaces(&mut self, spaces: usize) -> Result<(), W::Error> {
         let mut inserted = 0;
         while inserted < spaces {
             let insert = cmp::min(SPACES.len(), spaces - inserted);
-            inserted += out.write_str(&SPACES[..insert])?;
+            self.write_text(&SPACES[..insert])?;
+            inserted += insert;
         }
+        Ok(())
+    }
+}
+
+/// Appends to the string directly, without going through `std::fmt::Write`
+impl Render for String {
+    type Error = std::convert::Infallible;
+
+    fn write_str(&mut self, s: &str) -> Result<usize, Self::Error> {
+        self.push_str(s);
+        Ok(s.len())
+    }
 
+    fn write_str_all(&mut self, s: &str) -> Result<(), Self::Error> {
+        self.push_str(s);
         Ok(())
     }
 
-    fn fitting<'a, 'd, T, A>(
+    fn write_zero_error(&self) -> Self::Error {
+        unreachable!("everything is written")
+    }
+}
+
+impl<A> RenderAnnotated<A> for String {
+    fn push_annotation(&mut self, _: &A) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn pop_annotation(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// Appends the UTF-8 encoded output to the vector directly, without going through
+/// `std::io::Write`
+impl Render for Vec<u8> {
+    type Error = std::convert::Infallible;
+
+    fn write_str(&mut self, s: &str) -> Result<usize, Self::Error> {
+        self.extend_from_slice(s.as_bytes());
+        Ok(s.len())
+    }
+
+    fn write_str_all(&mut self, s: &str) -> Result<(), Self::Error> {
+        self.extend_from_slice(s.as_bytes());
+        Ok(())
+    }
+
+    fn write_zero_error(&self) -> Self::Error {
+        unreachable!("everything is written")
+    }
+}
+
+impl<A> RenderAnnotated<A> for Vec<u8> {
+    fn push_annotation(&mut self, _: &A) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn pop_annotation(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// Estimates the size of the rendered document from the text it contains. Indentation and the
+/// output of `column`, `nesting` and `page_width` documents are not included.
+pub(crate) fn estimate_size<'a, T, A>(doc: &Doc<'a, T, A>) -> usize
+where
+    T: DocPtr<'a, A>,
+{
+    let mut size = 0;
+    let mut stack = vec![doc];
+    while let Some(doc) = stack.pop() {
+        match *doc {
+            Doc::Append(ref l, ref r) => {
+                stack.push(r);
+                stack.push(l);
+            }
+            // Assume the flat layout, which is shorter
+            Doc::FlatAlt(_, ref doc)
+            | Doc::Union(_, ref doc)
+            | Doc::Group(ref doc)
+            | Doc::Nest(_, ref doc)
+            | Doc::Annotated(_, ref doc) => stack.push(doc),
+            Doc::Line => size += 1,
+            Doc::OwnedText(ref s) => size += s.len(),
+            Doc::BorrowedText(s) => size += s.len(),
+            Doc::SmallText(ref s) => size += s.len(),
+            Doc::Nil | Doc::Column(_) | Doc::Nesting(_) | Doc::PageWidth(_) | Doc::Lazy(_) => {}
+        }
+    }
+    size
+}
+
+/// Discards everything written to it, used to lay out a document without producing any output
+pub(crate) struct Discard;
+
+impl Render for Discard {
+    type Error = std::convert::Infallible;
+
+    fn write_str(&mut self, s: &str) -> Result<usize, Self::Error> {
+        Ok(s.len())
+    }
+
+    fn write_zero_error(&self) -> Self::Error {
+        unreachable!("everything is written")
+    }
+}
+
+impl<A> RenderAnnotated<A> for Discard {
+    fn push_annotation(&mut self, _: &A) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn pop_annotation(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// Returns the width of the widest line of `doc` when rendered with an unlimited width
+pub(crate) fn measure<'a, T, A>(doc: &T) -> usize
+where
+    T: DocPtr<'a, A> + 'a,
+    A: 'a,
+{
+    struct Measure {
+        pos: usize,
+        max: usize,
+    }
+
+    impl Render for Measure {
+        type Error = std::convert::Infallible;
+
+        fn write_str(&mut self, s: &str) -> Result<usize, Self::Error> {
+            let mut lines = s.split('\n');
+            if let Some(first) = lines.next() {
+                self.pos += first.len();
+            }
+            for line in lines {
+                self.pos = line.len();
+            }
+            self.max = self.max.max(self.pos);
+            Ok(s.len())
+        }
+
+        fn write_zero_error(&self) -> Self::Error {
+            unreachable!("everything is written")
+        }
+    }
+
+    impl<A> RenderAnnotated<A> for Measure {
+        fn push_annotation(&mut self, _: &A) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn pop_annotation(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    let mut measure = Measure { pos: 0, max: 0 };
+    match doc.render_raw(usize::MAX, &mut measure) {
+        Ok(()) => measure.max,
+        Err(never) => match never {},
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub(crate) enum Mode {
+    Break,
+    Flat,
+}
+
+pub(crate) type Cmd<'d, 'a, T, A> = (usize, Mode, &'d Doc<'a, T, A>);
+
+/// Checks whether `next` followed by the commands in `bcmds` fit on the rest of the line. If
+/// `trace` is given the index and position is pushed to it whenever the check moves on to a new
+/// command of `bcmds`. Returns the column reached when the check failed if it does not fit.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn fitting<'a, 'd, T, A>(
+    temp_arena: &'d typed_arena::Arena<T>,
+    next: &'d Doc<'a, T, A>,
+    bcmds: &[Cmd<'d, 'a, T, A>],
+    fcmds: &mut Vec<&'d Doc<'a, T, A>>,
+    mut pos: usize,
+    width: usize,
+    ind: usize,
+    newline_fits: fn(Mode) -> bool,
+    mut trace: Option<&mut Vec<(usize, usize)>>,
+) -> Result<(), usize>
+where
+    T: DocPtr<'a, A>,
+{
+    let mut bidx = bcmds.len();
+    fcmds.clear(); // clear from previous calls from best
+    fcmds.push(next);
+
+    let mut mode = Mode::Flat;
+    loop {
+        let mut doc = match fcmds.pop() {
+            None => {
+                if bidx == 0 {
+                    // All commands have been processed
+                    return Ok(());
+                } else {
+                    bidx -= 1;
+                    if let Some(trace) = trace.as_mut() {
+                        trace.push((bidx, pos));
+                    }
+                    mode = Mode::Break;
+                    bcmds[bidx].2
+                }
+            }
+            Some(cmd) => cmd,
+        };
+
+        loop {
+            match *doc {
+                Doc::Nil => {}
+                Doc::Append(ref ldoc, ref rdoc) => {
+                    fcmds.push(rdoc);
+                    // Since appended documents often appear in sequence on the left side we
+                    // gain a slight performance increase by batching these pushes (avoiding
+                    // to push and directly pop `Append` documents)
+                    doc = ldoc;
+                    while let Doc::Append(ref l, ref r) = *doc {
+                        fcmds.push(r);
+                        doc = l;
+                    }
+                    continue;
+                }
+                // Newlines inside the group makes it not fit, but those outside lets it
+                // fit on the current line
+                Doc::Line => {
+                    return if newline_fits(mode) { Ok(()) } else { Err(pos) };
+                }
+                Doc::BorrowedText(str) => {
+                    pos += str.len();
+                    if pos > width {
+                        return Err(pos);
+                    }
+                }
+                Doc::OwnedText(ref str) => {
+                    pos += str.len();
+                    if pos > width {
+                        return Err(pos);
+                    }
+                }
+                Doc::SmallText(ref str) => {
+                    pos += str.len();
+                    if pos > width {
+                        return Err(pos);
+                    }
+                }
+                Doc::FlatAlt(ref b, ref f) => {
+                    doc = match mode {
+                        Mode::Break => b,
+                        Mode::Flat => f,
+                    };
+                    continue;
+                }
+
+                Doc::Column(ref f) => {
+                    doc = temp_arena.alloc(f(pos));
+                    continue;
+                }
+                Doc::Nesting(ref f) => {
+                    doc = temp_arena.alloc(f(ind));
+                    continue;
+                }
+                Doc::PageWidth(ref f) | Doc::Lazy(ref f) => {
+                    doc = temp_arena.alloc(f(width));
+                    continue;
+                }
+                Doc::Nest(_, ref next)
+                | Doc::Group(ref next)
+                | Doc::Annotated(_, ref next)
+                | Doc::Union(_, ref next) => {
+                    doc = next;
+                    continue;
+                }
+            }
+            break;
+        }
+    }
+}
+
+/// The address of a group together with the column and indentation it starts at
+pub(crate) type GroupKey = (usize, usize, usize);
+
+/// Output written while laying out a group, see `GroupCache`
+#[derive(Clone, Debug)]
+pub(crate) enum Event<S, A> {
+    Text(S),
+    /// A line break followed by the indentation
+    Line(usize),
+    PushAnnotation(A),
+    PopAnnotation,
+}
+
+/// The layout of a group stored in a `GroupCache`
+pub(crate) struct CachedGroup<A> {
+    /// The output of the group
+    pub(crate) events: Vec<Event<String, A>>,
+    /// The column and indentation of each check which looked past the end of the group, together
+    /// with its result. The layout can only be reused if the checks still give the same results.
+    pub(crate) lookaheads: Vec<(usize, usize, bool)>,
+}
+
+/// Reuses the layout of groups, see `incremental::LayoutCache`. `Best` consults the cache before
+/// laying out a group in break mode and reports everything the layout of the group depends on.
+///
+/// Groups created by `column`, `nesting` and `page_width` only live for a single render so they
+/// are never looked up.
+pub(crate) trait GroupCache<A> {
+    /// Returns the layout stored for the group at `key`
+    fn get(&self, key: GroupKey) -> Option<&CachedGroup<A>>;
+    /// Called after the layout stored for the group at `key` has been written
+    fn reuse(&mut self, key: GroupKey);
+    /// Called when the group at `key` starts being laid out. It has been laid out once the command
+    /// stack is back to `base` commands with `annotations` annotations open.
+    fn start(&mut self, key: GroupKey, base: usize, annotations: usize);
+    /// Called with the result of each check deciding the layout of a group or union. `trace` holds
+    /// the index of each command on the stack the check looked at and the column it started at.
+    fn check(&mut self, trace: &[(usize, usize)], ind: usize, fits: bool);
+    /// Called with everything written
+    fn record(&mut self, event: Event<&str, &A>);
+    /// Called after each command with the size of the command stack and the number of open
+    /// annotations
+    fn end(&mut self, base: usize, annotations: usize);
+}
+
+#[inline]
+pub fn best<'a, W, T, A>(
+    doc: &Doc<'a, T, A>,
+    options: &RenderOptions,
+    out: &mut W,
+) -> Result<RenderStats, W::Error>
+where
+    T: DocPtr<'a, A> + 'a,
+    W: ?Sized + RenderAnnotated<A>,
+{
+    let temp_arena = typed_arena::Arena::new();
+    let mut out = Output::new(out, options);
+    let mut best = Best::new(&temp_arena, doc, options.width);
+    best.run(&mut out, |_| false)?;
+    best.finish(&mut out)
+}
+
+/// Renders like `best`, calling `trace` with each layout decision
+pub(crate) fn traced<'a, W, T, A>(
+    doc: &Doc<'a, T, A>,
+    options: &RenderOptions,
+    out: &mut W,
+    trace: &mut dyn FnMut(&Decision),
+) -> Result<RenderStats, W::Error>
+where
+    T: DocPtr<'a, A> + 'a,
+    W: ?Sized + RenderAnnotated<A>,
+{
+    let temp_arena = typed_arena::Arena::new();
+    let mut out = Output::new(out, options);
+    let mut best = Best::new(&temp_arena, doc, options.width).trace(trace);
+    best.run(&mut out, |_| false)?;
+    best.finish(&mut out)
+}
+
+/// The state of the renderer, which can be paused between commands
+pub(crate) struct Best<'d, 'a, T, A>
+where
+    T: DocPtr<'a, A>,
+{
+    temp_arena: &'d typed_arena::Arena<T>,
+    width: usize,
+    stats: RenderStats,
+    pos: usize,
+    bcmds: Vec<Cmd<'d, 'a, T, A>>,
+    fcmds: Vec<&'d Doc<'a, T, A>>,
+    annotation_levels: Vec<usize>,
+    trace: Option<&'d mut dyn FnMut(&Decision)>,
+    cache: Option<&'d mut dyn GroupCache<A>>,
+    /// The commands of `bcmds` which were checked by the last call to `fitting`, if there is a
+    /// cache to report them to
+    checked: Vec<(usize, usize)>,
+    /// The size of the command stack when a `column`, `nesting` or `page_width` document was
+    /// expanded. The documents on the stack above it only live for this render.
+    temp_level: Option<usize>,
+}
+
+impl<'d, 'a, T, A> Best<'d, 'a, T, A>
+where
+    T: DocPtr<'a, A> + 'a,
+{
+    pub(crate) fn new(
         temp_arena: &'d typed_arena::Arena<T>,
-        next: &'d Doc<'a, T, A>,
-        bcmds: &[Cmd<'d, 'a, T, A>],
-        fcmds: &mut Vec<&'d Doc<'a, T, A>>,
-        mut pos: usize,
+        doc: &'d Doc<'a, T, A>,
         width: usize,
-        ind: usize,
-        newline_fits: fn(Mode) -> bool,
-    ) -> bool
+    ) -> Self {
+        Best {
+            temp_arena,
+            width,
+            stats: RenderStats::default(),
+            pos: 0,
+            bcmds: vec![(0, Mode::Break, doc)],
+            fcmds: vec![],
+            annotation_levels: vec![],
+            trace: None,
+            cache: None,
+            checked: vec![],
+            temp_level: None,
+        }
+    }
+
+    /// Calls `trace` with each decision made for a `group` or `union`
+    pub(crate) fn trace(mut self, trace: &'d mut dyn FnMut(&Decision)) -> Self {
+        self.trace = Some(trace);
+        self
+    }
+
+    /// Reuses the layout of the groups stored in `cache` and stores the layout of the others
+    pub(crate) fn cache(mut self, cache: &'d mut dyn GroupCache<A>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Renders the document until it is done or until `pause` returns `true`, which is checked
+    /// before each command. Returns whether the whole document has been rendered.
+    pub(crate) fn run<W>(
+        &mut self,
+        out: &mut Output<'_, 'd, W, A>,
+        mut pause: impl FnMut(&W) -> bool,
+    ) -> Result<bool, W::Error>
     where
-        T: DocPtr<'a, A>,
+        W: ?Sized + RenderAnnotated<A>,
     {
-        let mut bidx = bcmds.len();
-        fcmds.clear(); // clear from previous calls from best
-        fcmds.push(next);
+        let Best {
+            temp_arena,
+            width,
+            ref mut stats,
+            ref mut pos,
+            ref mut bcmds,
+            ref mut fcmds,
+            ref mut annotation_levels,
+            ref mut trace,
+            ref mut cache,
+            ref mut checked,
+            ref mut temp_level,
+        } = *self;
 
-        let mut mode = Mode::Flat;
         loop {
-            let mut doc = match fcmds.pop() {
-                None => {
-                    if bidx == 0 {
-                        // All commands have been processed
-                        return true;
-                    } else {
-                        bidx -= 1;
-                        mode = Mode::Break;
-                        bcmds[bidx].2
-                    }
-                }
+            if out.done {
+                return Ok(true);
+            }
+            if pause(out.out) {
+                return Ok(false);
+            }
+            let mut cmd = match bcmds.pop() {
                 Some(cmd) => cmd,
+                None => return Ok(true),
             };
-
             loop {
+                let (ind, mode, doc) = cmd;
                 match *doc {
                     Doc::Nil => {}
                     Doc::Append(ref ldoc, ref rdoc) => {
-                        fcmds.push(rdoc);
-                        // Since appended documents often appear in sequence on the left side we
-                        // gain a slight performance increase by batching these pushes (avoiding
-                        // to push and directly pop `Append` documents)
-                        doc = ldoc;
-                        while let Doc::Append(ref l, ref r) = *doc {
-                            fcmds.push(r);
+                        bcmds.push((ind, mode, rdoc));
+                        let mut doc = ldoc;
+                        while let Doc::Append(ref l, ref r) = **doc {
+                            bcmds.push((ind, mode, r));
                             doc = l;
                         }
+                        cmd = (ind, mode, doc);
                         continue;
                     }
-                    // Newlines inside the group makes it not fit, but those outside lets it
-                    // fit on the current line
-                    Doc::Line => return newline_fits(mode),
-                    Doc::BorrowedText(ref str) => {
-                        pos += str.len();
-                        if pos > width {
-                            return false;
+                    Doc::FlatAlt(ref b, ref f) => {
+                        cmd = (
+                            ind,
+                            mode,
+                            match mode {
+                                Mode::Break => b,
+                                Mode::Flat => f,
+                            },
+                        );
+                        continue;
+                    }
+                    Doc::Group(ref inner) => match mode {
+                        Mode::Flat => {
+                            cmd = (ind, Mode::Flat, inner);
+                            continue;
+                        }
+                        Mode::Break => {
+                            if let (None, Some(cache)) = (*temp_level, cache.as_mut()) {
+                                let key = (doc as *const Doc<'a, T, A> as usize, *pos, ind);
+                                if let Some(group) = cache.get(key) {
+                                    let lookaheads = group.lookaheads.clone();
+                                    let mut valid = true;
+                                    for (pos, ind, fits) in lookaheads {
+                                        checked.clear();
+                                        let actual = fitting(
+                                            temp_arena,
+                                            &Doc::Nil,
+                                            bcmds,
+                                            fcmds,
+                                            pos,
+                                            width,
+                                            ind,
+                                            |mode| mode == Mode::Break,
+                                            Some(checked),
+                                        )
+                                        .is_ok();
+                                        cache.check(checked, ind, actual);
+                                        if actual != fits {
+                                            valid = false;
+                                            break;
+                                        }
+                                    }
+
+                                    if valid {
+                                        let group = cache.get(key).expect("the group is cached");
+                                        for event in &group.events {
+                                            match *event {
+                                                Event::Text(ref s) => {
+                                                    out.write_text(s)?;
+                                                    *pos += s.len();
+                                                }
+                                                Event::Line(ind) => {
+                                                    stats.end_line(*pos, width);
+                                                    out.write_newline(ind)?;
+                                                    *pos = ind;
+                                                }
+                                                Event::PushAnnotation(ref ann) => {
+                                                    out.push_replayed_annotation(ann)?
+                                                }
+                                                Event::PopAnnotation => {
+                                                    out.pop_replayed_annotation()?
+                                                }
+                                            }
+                                        }
+                                        cache.reuse(key);
+                                        break;
+                                    }
+                                }
+                                cache.start(key, bcmds.len(), annotation_levels.len());
+                            }
+
+                            checked.clear();
+                            let fits = fitting(
+                                temp_arena,
+                                inner,
+                                bcmds,
+                                fcmds,
+                                *pos,
+                                width,
+                                ind,
+                                |mode| mode == Mode::Break,
+                                cache.as_ref().map(|_| &mut *checked),
+                            );
+                            if let Some(cache) = cache.as_mut() {
+                                cache.check(checked, ind, fits.is_ok());
+                            }
+                            if let Some(trace) = trace.as_mut() {
+                                let line = stats.lines;
+                                let decision = Decision::new(
+                                    DecisionKind::Group,
+                                    fits,
+                                    line,
+                                    *pos,
+                                    ind,
+                                    width,
+                                );
+                                trace(&decision);
+                            }
+                            cmd = match fits {
+                                Ok(()) => (ind, Mode::Flat, &**inner),
+                                Err(_) => (ind, Mode::Break, inner),
+                            };
+                            continue;
                         }
+                    },
+                    Doc::Nest(off, ref doc) => {
+                        cmd = ((ind as isize).saturating_add(off) as usize, mode, doc);
+                        continue;
                     }
-                    Doc::OwnedText(ref str) => {
-                        pos += str.len();
-                        if pos > width {
-                            return false;
+                    Doc::Line => {
+                        stats.end_line(*pos, width);
+                        out.write_newline(ind)?;
+                        *pos = ind;
+                        if let Some(cache) = cache.as_mut() {
+                            cache.record(Event::Line(ind));
                         }
                     }
-                    Doc::SmallText(ref str) => {
-                        pos += str.len();
-                        if pos > width {
-                            return false;
+                    Doc::OwnedText(ref s) => {
+                        out.write_text(s)?;
+                        *pos += s.len();
+                        if let Some(cache) = cache.as_mut() {
+                            cache.record(Event::Text(s));
                         }
                     }
-                    Doc::FlatAlt(ref b, ref f) => {
-                        doc = match mode {
-                            Mode::Break => b,
-                            Mode::Flat => f,
+                    Doc::BorrowedText(s) => {
+                        out.write_text(s)?;
+                        *pos += s.len();
+                        if let Some(cache) = cache.as_mut() {
+                            cache.record(Event::Text(s));
+                        }
+                    }
+                    Doc::SmallText(ref s) => {
+                        out.write_text(s)?;
+                        *pos += s.len();
+                        if let Some(cache) = cache.as_mut() {
+                            cache.record(Event::Text(s));
+                        }
+                    }
+                    Doc::Annotated(ref ann, ref doc) => {
+                        out.push_annotation(ann)?;
+                        if let Some(cache) = cache.as_mut() {
+                            cache.record(Event::PushAnnotation(ann));
+                        }
+                        annotation_levels.push(bcmds.len());
+                        cmd = (ind, mode, doc);
+                        continue;
+                    }
+                    Doc::Union(ref l, ref r) => {
+                        checked.clear();
+                        let fits = fitting(
+                            temp_arena,
+                            l,
+                            bcmds,
+                            fcmds,
+                            *pos,
+                            width,
+                            ind,
+                            |_| true,
+                            cache.as_ref().map(|_| &mut *checked),
+                        );
+                        if let Some(cache) = cache.as_mut() {
+                            cache.check(checked, ind, fits.is_ok());
+                        }
+                        if let Some(trace) = trace.as_mut() {
+                            let line = stats.lines;
+                            let decision =
+                                Decision::new(DecisionKind::Union, fits, line, *pos, ind, width);
+                            trace(&decision);
+                        }
+                        cmd = match fits {
+                            Ok(()) => (ind, mode, l),
+                            Err(_) => (ind, mode, r),
                         };
                         continue;
                     }
-
                     Doc::Column(ref f) => {
-                        doc = temp_arena.alloc(f(pos));
+                        temp_level.get_or_insert(bcmds.len());
+                        cmd = (ind, mode, temp_arena.alloc(f(*pos)));
                         continue;
                     }
                     Doc::Nesting(ref f) => {
-                        doc = temp_arena.alloc(f(ind));
+                        temp_level.get_or_insert(bcmds.len());
+                        cmd = (ind, mode, temp_arena.alloc(f(ind)));
                         continue;
                     }
-                    Doc::Nest(_, ref next)
-                    | Doc::Group(ref next)
-                    | Doc::Annotated(_, ref next)
-                    | Doc::Union(_, ref next) => {
-                        doc = next;
+                    Doc::PageWidth(ref f) | Doc::Lazy(ref f) => {
+                        temp_level.get_or_insert(bcmds.len());
+                        cmd = (ind, mode, temp_arena.alloc(f(width)));
                         continue;
                     }
                 }
+
                 break;
             }
+            loop {
+                // Groups and annotations which end at the same command are closed innermost first
+                if let Some(cache) = cache.as_mut() {
+                    cache.end(bcmds.len(), annotation_levels.len());
+                }
+                if annotation_levels.last() != Some(&bcmds.len()) {
+                    break;
+                }
+                annotation_levels.pop();
+                out.pop_annotation()?;
+                if let Some(cache) = cache.as_mut() {
+                    cache.record(Event::PopAnnotation);
+                }
+            }
+            if *temp_level == Some(bcmds.len()) {
+                *temp_level = None;
+            }
         }
     }
 
-    let temp_arena = typed_arena::Arena::new();
+    /// Ends the rendering once `run` is done
+    pub(crate) fn finish<W>(self, out: &mut Output<'_, 'd, W, A>) -> Result<RenderStats, W::Error>
+    where
+        W: ?Sized + RenderAnnotated<A>,
+    {
+        let mut stats = self.stats;
+        out.close_annotations()?;
+        stats.truncated = out.done;
+
+        if !out.done {
+            stats.end_line(self.pos, self.width);
+        }
+        Ok(stats)
+    }
+}
 
-    let mut pos = 0;
-    let mut bcmds = vec![(0, Mode::Break, doc)];
-    let mut fcmds = vec![];
-    let mut annotation_levels = vec![];
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    while let Some(mut cmd) = bcmds.pop() {
-        loop {
-            let (ind, mode, doc) = cmd;
-            match *doc {
-                Doc::Nil => {}
-                Doc::Append(ref ldoc, ref rdoc) => {
-                    bcmds.push((ind, mode, rdoc));
-                    let mut doc = ldoc;
-                    while let Doc::Append(ref l, ref r) = **doc {
-                        bcmds.push((ind, mode, r));
-                        doc = l;
-                    }
-                    cmd = (ind, mode, doc);
-                    continue;
-                }
-                Doc::FlatAlt(ref b, ref f) => {
-                    cmd = (
-                        ind,
-                        mode,
-                        match mode {
-                            Mode::Break => b,
-                            Mode::Flat => f,
-                        },
-                    );
-                    continue;
-                }
-                Doc::Group(ref doc) => match mode {
-                    Mode::Flat => {
-                        cmd = (ind, Mode::Flat, doc);
-                        continue;
-                    }
-                    Mode::Break => {
-                        cmd = if fitting(
-                            &temp_arena,
-                            doc,
-                            &bcmds,
-                            &mut fcmds,
-                            pos,
-                            width,
-                            ind,
-                            |mode| mode == Mode::Break,
-                        ) {
-                            (ind, Mode::Flat, &**doc)
-                        } else {
-                            (ind, Mode::Break, doc)
-                        };
-                        continue;
-                    }
-                },
-                Doc::Nest(off, ref doc) => {
-                    cmd = ((ind as isize).saturating_add(off) as usize, mode, doc);
-                    continue;
-                }
-                Doc::Line => {
-                    write_newline(ind, out)?;
-                    pos = ind;
-                }
-                Doc::OwnedText(ref s) => {
-                    out.write_str_all(s)?;
-                    pos += s.len();
-                }
-                Doc::BorrowedText(ref s) => {
-                    out.write_str_all(s)?;
-                    pos += s.len();
-                }
-                Doc::SmallText(ref s) => {
-                    out.write_str_all(s)?;
-                    pos += s.len();
-                }
-                Doc::Annotated(ref ann, ref doc) => {
-                    out.push_annotation(ann)?;
-                    annotation_levels.push(bcmds.len());
-                    cmd = (ind, mode, doc);
-                    continue;
-                }
-                Doc::Union(ref l, ref r) => {
-                    cmd = if fitting(&temp_arena, l, &bcmds, &mut fcmds, pos, width, ind, |_| {
-                        true
-                    }) {
-                        (ind, mode, l)
-                    } else {
-                        (ind, mode, r)
-                    };
-                    continue;
-                }
-                Doc::Column(ref f) => {
-                    cmd = (ind, mode, temp_arena.alloc(f(pos)));
-                    continue;
-                }
-                Doc::Nesting(ref f) => {
-                    cmd = (ind, mode, temp_arena.alloc(f(ind)));
-                    continue;
-                }
+    use crate::{BoxAllocator, BoxDoc, DocAllocator};
+
+    /// An `io::Write` which writes at most `max` bytes at a time, is interrupted before every
+    /// write and stops accepting output (by returning `Ok(0)`) once `limit` bytes are written
+    struct Faulty {
+        written: Vec<u8>,
+        max: usize,
+        limit: usize,
+        interrupted: bool,
+    }
+
+    impl Faulty {
+        fn new(max: usize, limit: usize) -> Self {
+            Faulty {
+                written: Vec::new(),
+                max,
+                limit,
+                interrupted: false,
+            }
+        }
+    }
+
+    impl io::Write for Faulty {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if !self.interrupted {
+                self.interrupted = true;
+                return Err(io::ErrorKind::Interrupted.into());
             }
+            self.interrupted = false;
+            let count = buf.len().min(self.max).min(self.limit - self.written.len());
+            self.written.extend_from_slice(&buf[..count]);
+            Ok(count)
+        }
 
-            break;
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
         }
-        while annotation_levels.last() == Some(&bcmds.len()) {
-            annotation_levels.pop();
-            out.pop_annotation()?;
+    }
+
+    /// A `fmt::Write` which fails once `limit` bytes are written
+    struct FaultyFmt {
+        written: String,
+        limit: usize,
+    }
+
+    impl fmt::Write for FaultyFmt {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            if self.written.len() + s.len() > self.limit {
+                return Err(fmt::Error);
+            }
+            self.written.push_str(s);
+            Ok(())
+        }
+    }
+
+    /// Only forwards `write_str` so that the default `write_str_all` is used
+    struct WriteStrOnly<R>(R);
+
+    impl<R> Render for WriteStrOnly<R>
+    where
+        R: Render,
+    {
+        type Error = R::Error;
+
+        fn write_str(&mut self, s: &str) -> Result<usize, R::Error> {
+            self.0.write_str(s)
+        }
+
+        fn write_zero_error(&self) -> R::Error {
+            self.0.write_zero_error()
         }
     }
 
-    Ok(())
+    impl<R, A> RenderAnnotated<A> for WriteStrOnly<R>
+    where
+        R: RenderAnnotated<A>,
+    {
+        fn push_annotation(&mut self, annotation: &A) -> Result<(), R::Error> {
+            self.0.push_annotation(annotation)
+        }
+
+        fn pop_annotation(&mut self) -> Result<(), R::Error> {
+            self.0.pop_annotation()
+        }
+    }
+
+    /// Writes a single character at a time and nothing after `limit` characters
+    struct CharAtATime {
+        written: String,
+        limit: usize,
+    }
+
+    impl Render for CharAtATime {
+        type Error = &'static str;
+
+        fn write_str(&mut self, s: &str) -> Result<usize, Self::Error> {
+            if self.written.chars().count() == self.limit {
+                return Ok(0);
+            }
+            let c = s.chars().next().unwrap();
+            self.written.push(c);
+            Ok(c.len_utf8())
+        }
+
+        fn write_zero_error(&self) -> Self::Error {
+            "write zero"
+        }
+    }
+
+    impl RenderAnnotated<()> for CharAtATime {
+        fn push_annotation(&mut self, _: &()) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn pop_annotation(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    /// Non-ASCII text with an indentation wider than `SPACES`
+    fn doc<A: Clone>() -> BoxDoc<'static, A> {
+        BoxAllocator
+            .text("ünïcödé {")
+            .append(
+                BoxAllocator
+                    .hardline()
+                    .append(
+                        BoxAllocator.intersperse(vec!["αβγ", "δ", "€"], BoxAllocator.hardline()),
+                    )
+                    .nest(150),
+            )
+            .append(BoxAllocator.hardline())
+            .append("}")
+            .into_doc()
+    }
+
+    fn expected() -> String {
+        doc::<()>().pretty(80).to_string()
+    }
+
+    #[test]
+    fn io_write_short_writes() {
+        for max in 1..5 {
+            let mut out = Faulty::new(max, usize::MAX);
+            doc::<()>()
+                .render_raw(80, &mut IoWrite::new(&mut out))
+                .unwrap();
+            assert_eq!(String::from_utf8(out.written).unwrap(), expected());
+
+            let mut out = Faulty::new(max, usize::MAX);
+            doc::<()>()
+                .render_raw(80, &mut WriteStrOnly(IoWrite::new(&mut out)))
+                .unwrap();
+            assert_eq!(String::from_utf8(out.written).unwrap(), expected());
+        }
+
+        // A character is never split between two calls to `write_str`
+        let mut out = Faulty::new(1, usize::MAX);
+        assert_eq!(IoWrite::new(&mut out).write_str("äb").unwrap(), 2);
+        assert_eq!(out.written, "ä".as_bytes());
+    }
+
+    #[test]
+    fn io_write_zero() {
+        for limit in [0, 5, 200] {
+            let mut out = Faulty::new(3, limit);
+            let err = doc::<()>()
+                .render_raw(80, &mut IoWrite::new(&mut out))
+                .unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::WriteZero);
+
+            let mut out = Faulty::new(3, limit);
+            let err = doc::<()>()
+                .render_raw(80, &mut WriteStrOnly(IoWrite::new(&mut out)))
+                .unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::WriteZero);
+            assert_eq!(out.written.len(), limit);
+        }
+    }
+
+    #[test]
+    fn fmt_write_errors() {
+        let mut out = FaultyFmt {
+            written: String::new(),
+            limit: usize::MAX,
+        };
+        doc::<()>()
+            .render_raw(80, &mut WriteStrOnly(FmtWrite::new(&mut out)))
+            .unwrap();
+        assert_eq!(out.written, expected());
+
+        for limit in [0, 5, 200] {
+            let mut out = FaultyFmt {
+                written: String::new(),
+                limit,
+            };
+            assert_eq!(
+                doc::<()>().render_raw(80, &mut FmtWrite::new(&mut out)),
+                Err(fmt::Error)
+            );
+            assert!(out.written.len() <= limit);
+        }
+    }
+
+    #[test]
+    fn default_write_str_all() {
+        let mut out = CharAtATime {
+            written: String::new(),
+            limit: usize::MAX,
+        };
+        doc::<()>().render_raw(80, &mut out).unwrap();
+        assert_eq!(out.written, expected());
+
+        let mut out = CharAtATime {
+            written: String::new(),
+            limit: 20,
+        };
+        assert_eq!(doc::<()>().render_raw(80, &mut out), Err("write zero"));
+        assert_eq!(out.written.chars().count(), 20);
+    }
+
+    /// Records the annotations as `[` and `]`
+    struct Brackets(String);
+
+    impl Render for Brackets {
+        type Error = fmt::Error;
+
+        fn write_str(&mut self, s: &str) -> Result<usize, fmt::Error> {
+            self.0.push_str(s);
+            Ok(s.len())
+        }
+
+        fn write_zero_error(&self) -> fmt::Error {
+            fmt::Error
+        }
+    }
+
+    impl RenderAnnotated<()> for Brackets {
+        fn push_annotation(&mut self, _: &()) -> Result<(), fmt::Error> {
+            self.0.push('[');
+            Ok(())
+        }
+
+        fn pop_annotation(&mut self) -> Result<(), fmt::Error> {
+            self.0.push(']');
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn adapters() {
+        let doc = BoxAllocator
+            .text("a <b>")
+            .append(BoxAllocator.hardline().append("c & d").nest(2).annotate(()))
+            .append(BoxAllocator.hardline())
+            .append("é");
+
+        let mut out = Tee::new(
+            Counting::new(Brackets(String::new())),
+            MapText::new(FmtWrite::new(String::new()), |s| {
+                if s.contains(['<', '>', '&']) {
+                    Cow::Owned(
+                        s.replace('&', "&amp;")
+                            .replace('<', "&lt;")
+                            .replace('>', "&gt;"),
+                    )
+                } else {
+                    Cow::Borrowed(s)
+                }
+            }),
+        );
+        doc.1.render_raw(80, &mut out).unwrap();
+
+        let (counting, escaped) = out.into_inner();
+        assert_eq!(counting.bytes(), "a <b>\n  c & d\né".len());
+        assert_eq!(counting.lines(), 3);
+        assert_eq!(counting.column(), "é".len());
+        assert_eq!(counting.into_inner().0, "a <b>[\n  c & d]\né");
+        assert_eq!(escaped.into_inner().upstream, "a &lt;b&gt;\n  c &amp; d\né");
+    }
+
+    #[test]
+    fn counting_short_writes() {
+        let mut out = Faulty::new(3, usize::MAX);
+        let mut counting = Counting::new(WriteStrOnly(IoWrite::new(&mut out)));
+        doc::<()>().render_raw(80, &mut counting).unwrap();
+        assert_eq!(counting.bytes(), expected().len());
+        assert_eq!(counting.lines(), 5);
+        assert_eq!(counting.column(), 1);
+    }
+
+    #[cfg(feature = "termcolor")]
+    #[test]
+    fn term_colored_short_writes() {
+        let colored = || doc::<ColorSpec>().annotate(ColorSpec::new());
+
+        for max in 1..5 {
+            let mut out = Faulty::new(max, usize::MAX);
+            colored()
+                .render_raw(80, &mut TermColored::new(termcolor::NoColor::new(&mut out)))
+                .unwrap();
+            assert_eq!(String::from_utf8(out.written).unwrap(), expected());
+        }
+
+        let mut out = Faulty::new(3, 5);
+        let err = colored()
+            .render_raw(
+                80,
+                &mut WriteStrOnly(TermColored::new(termcolor::NoColor::new(&mut out))),
+            )
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WriteZero);
+    }
 }
@@ -0,0 +1,254 @@
+//! Rendering to asynchronous writers.
+//!
+//! The document is laid out synchronously, pausing whenever a chunk of output is ready so that it
+//! can be written before continuing. Only a single chunk of the output is kept in memory.
+
+use std::{
+    future::poll_fn,
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{
+    render::{Best, Output, RenderOptions},
+    Doc, DocPtr,
+};
+
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// The operations shared by the `AsyncWrite` traits of `futures` and `tokio`
+pub(crate) trait PollWrite {
+    fn poll_write(&mut self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>>;
+
+    fn poll_flush(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>>;
+}
+
+#[cfg(feature = "async")]
+pub(crate) struct FuturesWrite<'w, W: ?Sized>(pub(crate) &'w mut W);
+
+#[cfg(feature = "async")]
+impl<W> PollWrite for FuturesWrite<'_, W>
+where
+    W: ?Sized + futures_io::AsyncWrite + Unpin,
+{
+    fn poll_write(&mut self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut *self.0).poll_write(cx, buf)
+    }
+
+    fn poll_flush(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut *self.0).poll_flush(cx)
+    }
+}
+
+#[cfg(feature = "tokio")]
+pub(crate) struct TokioWrite<'w, W: ?Sized>(pub(crate) &'w mut W);
+
+#[cfg(feature = "tokio")]
+impl<W> PollWrite for TokioWrite<'_, W>
+where
+    W: ?Sized + tokio::io::AsyncWrite + Unpin,
+{
+    fn poll_write(&mut self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut *self.0).poll_write(cx, buf)
+    }
+
+    fn poll_flush(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut *self.0).poll_flush(cx)
+    }
+}
+
+async fn write_all<W>(out: &mut W, mut buf: &[u8]) -> io::Result<()>
+where
+    W: PollWrite,
+{
+    while !buf.is_empty() {
+        let count = poll_fn(|cx| out.poll_write(cx, buf)).await?;
+        if count == 0 {
+            return Err(io::ErrorKind::WriteZero.into());
+        }
+        buf = &buf[count..];
+    }
+    Ok(())
+}
+
+pub(crate) async fn render<'a, T, A, W>(
+    doc: &Doc<'a, T, A>,
+    width: usize,
+    mut out: W,
+) -> io::Result<()>
+where
+    T: DocPtr<'a, A> + 'a,
+    W: PollWrite,
+{
+    let temp_arena = typed_arena::Arena::new();
+    let options = RenderOptions::new(width);
+    let mut chunk = String::new();
+    let mut output = Output::new(&mut chunk, &options);
+    let mut best = Best::new(&temp_arena, doc, width);
+
+    loop {
+        let done = match best.run(&mut output, |chunk| chunk.len() >= CHUNK_SIZE) {
+            Ok(done) => done,
+            Err(never) => match never {},
+        };
+        write_all(&mut out, output.out.as_bytes()).await?;
+        output.out.clear();
+        if done {
+            break;
+        }
+    }
+    if let Err(never) = best.finish(&mut output) {
+        match never {}
+    }
+
+    poll_fn(|cx| out.poll_flush(cx)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        future::Future,
+        sync::Arc,
+        task::{Wake, Waker},
+    };
+
+    use super::*;
+
+    use crate::{Arena, DocAllocator};
+
+    struct Noop;
+
+    impl Wake for Noop {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let waker = Waker::from(Arc::new(Noop));
+        let mut cx = Context::from_waker(&waker);
+        let mut future = Box::pin(future);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    /// Returns `Pending` before every write, accepts at most 100 bytes at a time and nothing more
+    /// once `limit` bytes are written
+    #[derive(Default)]
+    struct Slow {
+        written: Vec<u8>,
+        limit: Option<usize>,
+        ready: bool,
+        writes: usize,
+        largest_write: usize,
+        flushed: bool,
+    }
+
+    impl Slow {
+        fn poll_write(&mut self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+            if !self.ready {
+                self.ready = true;
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            self.ready = false;
+            let limit = self.limit.unwrap_or(usize::MAX);
+            let count = buf.len().min(100).min(limit - self.written.len());
+            self.written.extend_from_slice(&buf[..count]);
+            self.writes += 1;
+            self.largest_write = self.largest_write.max(buf.len());
+            Poll::Ready(Ok(count))
+        }
+    }
+
+    #[cfg(feature = "async")]
+    impl futures_io::AsyncWrite for Slow {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.get_mut().poll_write(cx, buf)
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            self.get_mut().flushed = true;
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    impl tokio::io::AsyncWrite for Slow {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.get_mut().poll_write(cx, buf)
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            self.get_mut().flushed = true;
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn check(render: impl Fn(&crate::RefDoc<'_, ()>, &mut Slow) -> io::Result<()>) {
+        let arena = Arena::<()>::new();
+        let doc = arena
+            .intersperse(
+                (0..5000).map(|i| {
+                    arena
+                        .text("item")
+                        .append(arena.line())
+                        .append(arena.as_string(i))
+                        .group()
+                }),
+                arena.text(",").append(arena.line()),
+            )
+            .group()
+            .into_doc();
+
+        let mut out = Slow::default();
+        render(&doc, &mut out).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out.written).unwrap(),
+            doc.pretty(80).to_string()
+        );
+        assert!(out.flushed);
+        // The output is written in chunks
+        assert!(out.largest_write < CHUNK_SIZE + 100);
+        assert!(out.writes > 1);
+
+        let mut out = Slow {
+            limit: Some(10_000),
+            ..Slow::default()
+        };
+        let err = render(&doc, &mut out).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WriteZero);
+        assert_eq!(out.written.len(), 10_000);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn render_futures() {
+        check(|doc, out| block_on(doc.render_async(80, out)));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn render_tokio() {
+        check(|doc, out| block_on(doc.render_tokio(80, out)));
+    }
+}
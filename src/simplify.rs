@@ -0,0 +1,351 @@
+//! Normalization of documents, removing the structure which does not affect the output.
+
+use std::marker::PhantomData;
+
+use crate::{concat_balanced, BuildDoc, Doc, DocAllocator, DocPtr, SmallText};
+
+/// What a simplified document contains, which decides whether the `Group` or `Nest` around it has
+/// any effect. The closures of `Column`, `Nesting`, `PageWidth` and `Lazy` may return anything, so
+/// they count as both.
+#[derive(Clone, Copy, Default)]
+struct Contents {
+    /// A line break, which is indented by `Nest`
+    line: bool,
+    /// A `FlatAlt`, which is laid out according to the enclosing `Group`
+    flat_alt: bool,
+}
+
+impl Contents {
+    const ANY: Contents = Contents {
+        line: true,
+        flat_alt: true,
+    };
+}
+
+impl std::ops::BitOrAssign for Contents {
+    fn bitor_assign(&mut self, other: Contents) {
+        self.line |= other.line;
+        self.flat_alt |= other.flat_alt;
+    }
+}
+
+struct Simplify<'a, D, A>
+where
+    D: ?Sized,
+{
+    allocator: &'a D,
+    _marker: PhantomData<A>,
+}
+
+impl<'a, D, A> Simplify<'a, D, A>
+where
+    D: ?Sized + DocAllocator<'a, A>,
+    A: Clone + 'a,
+{
+    /// Pushes `doc` to the simplified documents in `parts`, merging text with the text before it
+    /// when the result is small enough to be stored inline
+    fn push(&self, parts: &mut Vec<Doc<'a, D::Doc, A>>, doc: Doc<'a, D::Doc, A>) {
+        if let (Some(last), Some(text)) = (parts.last_mut(), doc.as_text()) {
+            if let Some(prefix) = last.as_text() {
+                let mut merged = SmallText::new();
+                if merged.try_push_str(prefix).is_ok() && merged.try_push_str(text).is_ok() {
+                    *last = Doc::SmallText(merged);
+                    return;
+                }
+            }
+        }
+        parts.push(doc);
+    }
+
+    /// Concatenates the simplified documents of `parts`
+    fn concat(&self, parts: Vec<Doc<'a, D::Doc, A>>) -> Doc<'a, D::Doc, A> {
+        concat_balanced(self.allocator, parts.into_iter().map(BuildDoc::Doc)).into_plain_doc()
+    }
+
+    fn simplify(&self, doc: &Doc<'a, D::Doc, A>) -> (Doc<'a, D::Doc, A>, Contents) {
+        let mut parts = Vec::new();
+        let contents = self.sequence(doc, &mut parts);
+        (self.concat(parts), contents)
+    }
+
+    /// Pushes the simplified documents which are concatenated in `doc` to `parts`
+    fn sequence(&self, doc: &Doc<'a, D::Doc, A>, parts: &mut Vec<Doc<'a, D::Doc, A>>) -> Contents {
+        let mut contents = Contents::default();
+        // `Append` chains can be very long so they are flattened without recursion
+        let mut stack = vec![doc];
+        while let Some(doc) = stack.pop() {
+            match *doc {
+                Doc::Nil => {}
+                Doc::Append(ref l, ref r) => {
+                    stack.push(r);
+                    stack.push(l);
+                }
+                Doc::OwnedText(_) | Doc::BorrowedText(_) | Doc::SmallText(_) => {
+                    self.push(parts, doc.clone_text())
+                }
+                Doc::Line => {
+                    contents.line = true;
+                    parts.push(Doc::Line);
+                }
+                Doc::Group(ref inner) => {
+                    let mut inner_parts = Vec::new();
+                    let inner_contents = self.sequence(inner, &mut inner_parts);
+                    contents |= inner_contents;
+                    if !inner_contents.flat_alt {
+                        // Both layouts of the group are the same
+                        for part in inner_parts {
+                            self.push(parts, part);
+                        }
+                    } else if let [Doc::Group(_)] = inner_parts[..] {
+                        parts.append(&mut inner_parts);
+                    } else {
+                        let inner = self.concat(inner_parts);
+                        parts.push(Doc::Group(self.allocator.alloc(inner)));
+                    }
+                }
+                Doc::Nest(offset, ref inner) => {
+                    let mut inner_parts = Vec::new();
+                    let inner_contents = self.sequence(inner, &mut inner_parts);
+                    contents |= inner_contents;
+                    if offset == 0 || !inner_contents.line {
+                        // There is no line to indent
+                        for part in inner_parts {
+                            self.push(parts, part);
+                        }
+                    } else {
+                        let inner = self.concat(inner_parts);
+                        parts.push(Doc::Nest(offset, self.allocator.alloc(inner)));
+                    }
+                }
+                Doc::FlatAlt(ref b, ref f) => {
+                    let (b, b_contents) = self.simplify(b);
+                    let (f, f_contents) = self.simplify(f);
+                    contents |= b_contents;
+                    contents |= f_contents;
+                    contents.flat_alt = true;
+                    parts.push(Doc::FlatAlt(
+                        self.allocator.alloc(b),
+                        self.allocator.alloc(f),
+                    ));
+                }
+                Doc::Union(ref l, ref r) => {
+                    let (l, l_contents) = self.simplify(l);
+                    let (r, r_contents) = self.simplify(r);
+                    contents |= l_contents;
+                    contents |= r_contents;
+                    parts.push(Doc::Union(self.allocator.alloc(l), self.allocator.alloc(r)));
+                }
+                Doc::Annotated(ref ann, ref inner) => {
+                    let (inner, inner_contents) = self.simplify(inner);
+                    contents |= inner_contents;
+                    parts.push(Doc::Annotated(ann.clone(), self.allocator.alloc(inner)));
+                }
+                Doc::Column(ref f) => {
+                    contents |= Contents::ANY;
+                    parts.push(Doc::Column(f.clone()));
+                }
+                Doc::Nesting(ref f) => {
+                    contents |= Contents::ANY;
+                    parts.push(Doc::Nesting(f.clone()));
+                }
+                Doc::PageWidth(ref f) => {
+                    contents |= Contents::ANY;
+                    parts.push(Doc::PageWidth(f.clone()));
+                }
+                Doc::Lazy(ref f) => {
+                    contents |= Contents::ANY;
+                    parts.push(Doc::Lazy(f.clone()));
+                }
+            }
+        }
+        contents
+    }
+}
+
+impl<'a, T, A> Doc<'a, T, A>
+where
+    T: DocPtr<'a, A>,
+{
+    /// Copies a text document
+    fn clone_text(&self) -> Self {
+        match *self {
+            Doc::OwnedText(ref s) => Doc::OwnedText(s.clone()),
+            Doc::BorrowedText(s) => Doc::BorrowedText(s),
+            Doc::SmallText(s) => Doc::SmallText(s),
+            _ => unreachable!("not a text document"),
+        }
+    }
+}
+
+pub(crate) fn simplify<'a, D, A>(allocator: &'a D, doc: &Doc<'a, D::Doc, A>) -> Doc<'a, D::Doc, A>
+where
+    D: ?Sized + DocAllocator<'a, A>,
+    A: Clone + 'a,
+{
+    Simplify {
+        allocator,
+        _marker: PhantomData,
+    }
+    .simplify(doc)
+    .0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use proptest::prelude::*;
+
+    use crate::{Arena, BoxAllocator, BoxDoc, DocAllocator, Render, RenderAnnotated};
+
+    /// The structure of a generated document
+    #[derive(Clone, Debug)]
+    enum Shape {
+        Nil,
+        Text(&'static str),
+        HardLine,
+        Line,
+        Line_,
+        Concat(Vec<Shape>),
+        Group(Box<Shape>),
+        Nest(isize, Box<Shape>),
+        FlatAlt(Box<Shape>, Box<Shape>),
+        Union(Box<Shape>, Box<Shape>),
+        Annotated(u8, Box<Shape>),
+        Column,
+    }
+
+    fn shape() -> impl Strategy<Value = Shape> {
+        let leaf = prop_oneof![
+            Just(Shape::Nil),
+            prop::sample::select(vec![
+                "",
+                "a",
+                "bc",
+                "hello",
+                "a text which is too long to be small"
+            ])
+            .prop_map(Shape::Text),
+            Just(Shape::HardLine),
+            Just(Shape::Line),
+            Just(Shape::Line_),
+            Just(Shape::Column),
+        ];
+        leaf.prop_recursive(5, 48, 5, |inner| {
+            prop_oneof![
+                prop::collection::vec(inner.clone(), 0..6).prop_map(Shape::Concat),
+                inner.clone().prop_map(|s| Shape::Group(Box::new(s))),
+                (0isize..4, inner.clone()).prop_map(|(i, s)| Shape::Nest(i, Box::new(s))),
+                (inner.clone(), inner.clone())
+                    .prop_map(|(b, f)| Shape::FlatAlt(Box::new(b), Box::new(f))),
+                (inner.clone(), inner.clone())
+                    .prop_map(|(l, r)| Shape::Union(Box::new(l), Box::new(r))),
+                (0u8..3, inner).prop_map(|(a, s)| Shape::Annotated(a, Box::new(s))),
+            ]
+        })
+    }
+
+    fn build(shape: &Shape) -> BoxDoc<'static, u8> {
+        let a = &BoxAllocator;
+        let doc = match shape {
+            Shape::Nil => a.nil(),
+            Shape::Text(s) => a.text(*s),
+            Shape::HardLine => a.hardline(),
+            Shape::Line => a.line(),
+            Shape::Line_ => a.line_(),
+            Shape::Concat(shapes) => a.concat(shapes.iter().map(build)),
+            Shape::Group(s) => a.nil().append(build(s)).group(),
+            Shape::Nest(i, s) => a.nil().append(build(s)).nest(*i),
+            Shape::FlatAlt(b, f) => a.nil().append(build(b)).flat_alt(build(f)),
+            Shape::Union(l, r) => a.nil().append(build(l)).union(build(r)),
+            Shape::Annotated(ann, s) => a.nil().append(build(s)).annotate(*ann),
+            Shape::Column => a.column(BoxDoc::as_string),
+        };
+        doc.into_doc()
+    }
+
+    /// Writes the annotations as brackets
+    struct Marked(String);
+
+    impl Render for Marked {
+        type Error = Infallible;
+
+        fn write_str(&mut self, s: &str) -> Result<usize, Infallible> {
+            self.0.push_str(s);
+            Ok(s.len())
+        }
+
+        fn write_zero_error(&self) -> Infallible {
+            unreachable!("everything is written")
+        }
+    }
+
+    impl RenderAnnotated<u8> for Marked {
+        fn push_annotation(&mut self, ann: &u8) -> Result<(), Infallible> {
+            self.0.push_str(&format!("<{}", ann));
+            Ok(())
+        }
+
+        fn pop_annotation(&mut self) -> Result<(), Infallible> {
+            self.0.push('>');
+            Ok(())
+        }
+    }
+
+    fn render(doc: &BoxDoc<'static, u8>, width: usize) -> String {
+        let mut out = Marked(String::new());
+        match doc.render_raw(width, &mut out) {
+            Ok(()) => out.0,
+            Err(never) => match never {},
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn output_is_unchanged(shape in shape()) {
+            let doc = build(&shape);
+            let simplified = doc.simplify();
+            for width in [0, 3, 8, 20, 80] {
+                prop_assert_eq!(render(&simplified, width), render(&doc, width));
+            }
+        }
+    }
+
+    #[test]
+    fn normalizes_structure() {
+        let arena = Arena::<()>::new();
+        let doc = arena
+            .text("fn")
+            .append(" ")
+            .append("main")
+            .append(arena.text("()").nest(4).group().group())
+            .append(arena.hardline().append(arena.text("body")).nest(0).group())
+            .append(arena.line().append("x").nest(4).group().group());
+        let simplified = arena.simplify(&doc.1);
+        difference::assert_diff!(
+            &simplified.1.dump(),
+            r#"Append
+  Text("fn main()")
+  Line
+  Text("body")
+  Group
+    Nest(4)
+      Append
+        FlatAlt
+          Line
+          Text(" ")
+        Text("x")"#,
+            "\n",
+            0
+        );
+    }
+
+    #[test]
+    fn long_concat() {
+        let arena = Arena::<()>::new();
+        let doc =
+            arena.concat((0..100_000).map(|i| arena.text(if i % 2 == 0 { "a" } else { "b" })));
+        let simplified = arena.simplify(&doc.1);
+        assert_eq!(simplified.1.render_string(80), doc.1.render_string(80));
+    }
+}
@@ -0,0 +1,426 @@
+//! A streaming pretty printer which lays out a sequence of tokens using a lookahead bounded by the
+//! page width, so that documents do not need to be kept in memory to be rendered.
+
+use std::{borrow::Cow, cmp, collections::VecDeque};
+
+use crate::{
+    render::{Mode, SPACES},
+    RenderAnnotated,
+};
+
+/// A token of a streamed document. The tokens correspond to the documents of the same name and
+/// `GroupStart`/`GroupEnd`, `NestStart`/`NestEnd` and `AnnotationStart`/`AnnotationEnd` must be
+/// balanced.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Token<'a, A = ()> {
+    Text(Cow<'a, str>),
+    /// A line break, or the given text if the enclosing group is laid out on a single line (`" "`
+    /// for `line`, `""` for `line_`)
+    Line(&'a str),
+    /// A line break which is never laid out on a single line
+    HardLine,
+    GroupStart,
+    GroupEnd,
+    NestStart(isize),
+    NestEnd,
+    AnnotationStart(A),
+    AnnotationEnd,
+}
+
+/// The number of tokens, in addition to the page width, which may be buffered while the layout of
+/// a group is undecided
+const MAX_LOOKAHEAD: usize = 1024;
+
+/// Lays out a stream of tokens as it is pushed, choosing the same layout as `Doc::render`.
+///
+/// Tokens are only buffered while the layout of a group is undecided, which happens once the
+/// group and the text following it up to the next line break fit on the line or once the buffered
+/// text no longer fits. The buffered text is therefore never wider than the page, but tokens
+/// without width (the start and end of groups, nests and annotations, and empty text) do not
+/// decide anything. To keep the buffer bounded, a group which is still undecided after more tokens
+/// than the page width plus 1024 is laid out as broken, where `Doc::render` could have laid it out
+/// flat.
+pub struct StreamPrinter<'a, 'w, W, A>
+where
+    W: ?Sized,
+{
+    out: &'w mut W,
+    width: usize,
+    pos: usize,
+    indents: Vec<usize>,
+    /// The modes of the groups which have been started, with the outermost `Break` mode of the
+    /// document itself at the bottom
+    modes: Vec<Mode>,
+    buffer: VecDeque<Token<'a, A>>,
+}
+
+impl<'a, 'w, W, A> StreamPrinter<'a, 'w, W, A>
+where
+    W: ?Sized + RenderAnnotated<A>,
+{
+    pub fn new(width: usize, out: &'w mut W) -> Self {
+        StreamPrinter {
+            out,
+            width,
+            pos: 0,
+            indents: vec![0],
+            modes: vec![Mode::Break],
+            buffer: VecDeque::new(),
+        }
+    }
+
+    /// Lays out `token`, writing everything that can be decided to the output
+    pub fn push(&mut self, token: Token<'a, A>) -> Result<(), W::Error> {
+        self.buffer.push_back(token);
+        self.advance(false)
+    }
+
+    /// Writes the remaining tokens once the end of the document is reached
+    pub fn finish(mut self) -> Result<(), W::Error> {
+        self.advance(true)
+    }
+
+    fn mode(&self) -> Mode {
+        *self.modes.last().unwrap_or(&Mode::Break)
+    }
+
+    fn indent(&self) -> usize {
+        *self.indents.last().unwrap_or(&0)
+    }
+
+    fn advance(&mut self, end: bool) -> Result<(), W::Error> {
+        while let Some(token) = self.buffer.front() {
+            if matches!(token, Token::GroupStart) && self.mode() == Mode::Break {
+                match self.fits(end) {
+                    Some(fits) => {
+                        self.buffer.pop_front();
+                        self.modes.push(if fits { Mode::Flat } else { Mode::Break });
+                    }
+                    None => return Ok(()),
+                }
+            } else if let Some(token) = self.buffer.pop_front() {
+                self.emit(token)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks whether the group at the front of the buffer fits, or returns `None` if more tokens
+    /// are needed to tell. The check mirrors `render::fitting`: the group is laid out flat and
+    /// followed by the rest of the document in `Break` mode up to the next line break. Once too
+    /// many tokens are buffered the group is considered not to fit.
+    fn fits(&self, end: bool) -> Option<bool> {
+        let mut pos = self.pos;
+        // Skip the start of the group being checked
+        let mut depth = 1;
+        for token in self.buffer.iter().skip(1) {
+            let flat = depth > 0;
+            // Like `render::fitting`, the width is only checked after text, as a flat `line_` has
+            // no text
+            match *token {
+                Token::Text(ref s) => {
+                    pos += s.len();
+                    if pos > self.width {
+                        return Some(false);
+                    }
+                }
+                Token::Line(s) if flat => {
+                    if !s.is_empty() {
+                        pos += s.len();
+                        if pos > self.width {
+                            return Some(false);
+                        }
+                    }
+                }
+                Token::Line(_) => return Some(true),
+                Token::HardLine => return Some(!flat),
+                Token::GroupStart if flat => depth += 1,
+                Token::GroupEnd if flat => depth -= 1,
+                Token::GroupStart
+                | Token::GroupEnd
+                | Token::NestStart(_)
+                | Token::NestEnd
+                | Token::AnnotationStart(_)
+                | Token::AnnotationEnd => (),
+            }
+        }
+        if end {
+            Some(true)
+        } else if self.buffer.len() > self.width.saturating_add(MAX_LOOKAHEAD) {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    fn emit(&mut self, token: Token<'a, A>) -> Result<(), W::Error> {
+        match token {
+            Token::Text(s) => self.write_str(&s)?,
+            Token::Line(s) if self.mode() == Mode::Flat => self.write_str(s)?,
+            Token::Line(_) | Token::HardLine => {
+                self.out.write_str_all("\n")?;
+                self.pos = 0;
+                let mut inserted = 0;
+                let indent = self.indent();
+                while inserted < indent {
+                    let insert = cmp::min(SPACES.len(), indent - inserted);
+                    self.write_str(&SPACES[..insert])?;
+                    inserted += insert;
+                }
+            }
+            Token::GroupStart => self.modes.push(Mode::Flat),
+            Token::GroupEnd => {
+                self.modes.pop();
+            }
+            Token::NestStart(offset) => {
+                let indent = (self.indent() as isize).saturating_add(offset) as usize;
+                self.indents.push(indent);
+            }
+            Token::NestEnd => {
+                self.indents.pop();
+            }
+            Token::AnnotationStart(ann) => self.out.push_annotation(&ann)?,
+            Token::AnnotationEnd => self.out.pop_annotation()?,
+        }
+        Ok(())
+    }
+
+    fn write_str(&mut self, s: &str) -> Result<(), W::Error> {
+        self.pos += s.len();
+        self.out.write_str_all(s)
+    }
+}
+
+/// Lays out the tokens of `tokens` as they are produced, writing the output to `out`
+///
+/// ```
+/// use pretty::{render_stream, FmtWrite, Token};
+///
+/// let tokens = vec![
+///     Token::GroupStart,
+///     Token::Text("hello".into()),
+///     Token::Line(" "),
+///     Token::Text("world".into()),
+///     Token::GroupEnd,
+/// ];
+/// let mut s = String::new();
+/// render_stream::<_, (), _>(tokens.clone(), 80, &mut FmtWrite::new(&mut s)).unwrap();
+/// assert_eq!(s, "hello world");
+///
+/// let mut s = String::new();
+/// render_stream::<_, (), _>(tokens, 8, &mut FmtWrite::new(&mut s)).unwrap();
+/// assert_eq!(s, "hello\nworld");
+/// ```
+pub fn render_stream<'a, I, A, W>(tokens: I, width: usize, out: &mut W) -> Result<(), W::Error>
+where
+    I: IntoIterator<Item = Token<'a, A>>,
+    W: ?Sized + RenderAnnotated<A>,
+{
+    let mut printer = StreamPrinter::new(width, out);
+    for token in tokens {
+        printer.push(token)?;
+    }
+    printer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use proptest::prelude::*;
+
+    use crate::{Arena, Doc, DocAllocator, FmtWrite, RefDoc};
+
+    /// Converts the documents which have a token equivalent
+    fn tokens<'a>(doc: RefDoc<'a, ()>, out: &mut Vec<Token<'a, ()>>) {
+        match *doc {
+            Doc::Nil => (),
+            Doc::Append(l, r) => {
+                tokens(l, out);
+                tokens(r, out);
+            }
+            Doc::Group(inner) => {
+                out.push(Token::GroupStart);
+                tokens(inner, out);
+                out.push(Token::GroupEnd);
+            }
+            Doc::FlatAlt(b, f) => match (&*b, &*f) {
+                (Doc::Line, Doc::BorrowedText(s)) => out.push(Token::Line(s)),
+                (Doc::Line, Doc::Nil) => out.push(Token::Line("")),
+                _ => panic!("unsupported document"),
+            },
+            Doc::Nest(offset, inner) => {
+                out.push(Token::NestStart(offset));
+                tokens(inner, out);
+                out.push(Token::NestEnd);
+            }
+            Doc::Line => out.push(Token::HardLine),
+            Doc::BorrowedText(s) => out.push(Token::Text(s.into())),
+            Doc::OwnedText(ref s) => out.push(Token::Text(s.to_string().into())),
+            Doc::SmallText(ref s) => out.push(Token::Text(s.to_string().into())),
+            _ => panic!("unsupported document"),
+        }
+    }
+
+    fn test(doc: RefDoc<'_, ()>) {
+        let mut streamed = Vec::new();
+        tokens(doc, &mut streamed);
+        for width in 0..30 {
+            let mut s = String::new();
+            render_stream(streamed.clone(), width, &mut FmtWrite::new(&mut s)).unwrap();
+            difference::assert_diff!(&s, &doc.pretty(width).to_string(), "\n", 0);
+        }
+    }
+
+    fn call<'a>(
+        arena: &'a Arena<'a, ()>,
+        name: &'static str,
+        args: Vec<RefDoc<'a, ()>>,
+    ) -> RefDoc<'a, ()> {
+        arena
+            .text(name)
+            .append(
+                arena
+                    .line_()
+                    .append(arena.intersperse(args, arena.text(",").append(arena.line())))
+                    .nest(4)
+                    .append(arena.line_())
+                    .parens()
+                    .group(),
+            )
+            .into_doc()
+    }
+
+    #[test]
+    fn same_layout_as_best() {
+        let arena = Arena::<()>::new();
+        let inner = call(
+            &arena,
+            "g",
+            vec![arena.text("x").into_doc(), arena.text("y").into_doc()],
+        );
+        let doc = call(
+            &arena,
+            "f",
+            vec![
+                inner,
+                arena.reflow("some words to reflow").into_doc(),
+                call(&arena, "h", vec![]),
+            ],
+        );
+        test(doc);
+
+        let doc = arena
+            .text("fn main() {")
+            .append(arena.hardline().append(doc).nest(4))
+            .append(arena.hardline())
+            .append("}")
+            .group()
+            .append(arena.line())
+            .append("trailing")
+            .group()
+            .into_doc();
+        test(doc);
+    }
+
+    #[test]
+    fn lookahead_is_bounded() {
+        let mut s = String::new();
+        let mut out = FmtWrite::new(&mut s);
+        let mut printer = StreamPrinter::<_, ()>::new(10, &mut out);
+        for _ in 0..1000 {
+            printer.push(Token::GroupStart).unwrap();
+            printer.push(Token::Text("word".into())).unwrap();
+            printer.push(Token::Line(" ")).unwrap();
+            assert!(printer.buffer.len() <= 10);
+        }
+        for _ in 0..1000 {
+            printer.push(Token::GroupEnd).unwrap();
+        }
+        printer.finish().unwrap();
+        // Only the innermost group fits
+        assert_eq!(s.lines().count(), 999);
+        assert!(s.ends_with("\nword word "));
+    }
+
+    #[test]
+    fn lookahead_without_width_is_bounded() {
+        let mut s = String::new();
+        let mut out = FmtWrite::new(&mut s);
+        let mut printer = StreamPrinter::<_, ()>::new(10, &mut out);
+        printer.push(Token::GroupStart).unwrap();
+        printer.push(Token::Text("a".into())).unwrap();
+        printer.push(Token::Line(" ")).unwrap();
+        for _ in 0..10_000 {
+            printer.push(Token::NestStart(2)).unwrap();
+            printer.push(Token::AnnotationStart(())).unwrap();
+            printer.push(Token::Text("".into())).unwrap();
+            assert!(printer.buffer.len() <= 10 + MAX_LOOKAHEAD + 1);
+        }
+        printer.push(Token::Text("b".into())).unwrap();
+        for _ in 0..10_000 {
+            printer.push(Token::AnnotationEnd).unwrap();
+            printer.push(Token::NestEnd).unwrap();
+        }
+        printer.push(Token::GroupEnd).unwrap();
+        printer.finish().unwrap();
+        // The group is broken as it is still undecided once the buffer is full
+        assert_eq!(s, "a\nb");
+    }
+
+    #[test]
+    fn flat_line_without_text_fits_past_the_margin() {
+        let arena = Arena::<()>::new();
+        let doc = arena.text("hello").append(arena.line_().group()).into_doc();
+        test(doc);
+    }
+
+    /// The structure of a generated document
+    #[derive(Clone, Debug)]
+    enum Shape {
+        Text(&'static str),
+        Line,
+        Line_,
+        HardLine,
+        Concat(Vec<Shape>),
+        Group(Box<Shape>),
+        Nest(isize, Box<Shape>),
+    }
+
+    fn shape() -> impl Strategy<Value = Shape> {
+        let leaf = prop_oneof![
+            prop::sample::select(vec!["", "a", "bc", "hello"]).prop_map(Shape::Text),
+            Just(Shape::Line),
+            Just(Shape::Line_),
+            Just(Shape::HardLine),
+        ];
+        leaf.prop_recursive(4, 32, 4, |inner| {
+            prop_oneof![
+                prop::collection::vec(inner.clone(), 0..5).prop_map(Shape::Concat),
+                inner.clone().prop_map(|s| Shape::Group(Box::new(s))),
+                (0isize..3, inner).prop_map(|(i, s)| Shape::Nest(i, Box::new(s))),
+            ]
+        })
+    }
+
+    fn build<'a>(arena: &'a Arena<'a, ()>, shape: &Shape) -> RefDoc<'a, ()> {
+        let doc = match shape {
+            Shape::Text(s) => arena.text(*s),
+            Shape::Line => arena.line(),
+            Shape::Line_ => arena.line_(),
+            Shape::HardLine => arena.hardline(),
+            Shape::Concat(shapes) => arena.concat(shapes.iter().map(|s| build(arena, s))),
+            Shape::Group(s) => arena.nil().append(build(arena, s)).group(),
+            Shape::Nest(i, s) => arena.nil().append(build(arena, s)).nest(*i),
+        };
+        doc.into_doc()
+    }
+
+    proptest! {
+        #[test]
+        fn generated_same_layout_as_best(shape in shape()) {
+            let arena = Arena::<()>::new();
+            test(build(&arena, &shape));
+        }
+    }
+}
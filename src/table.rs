@@ -0,0 +1,285 @@
+//! Tables of documents whose columns are aligned across rows.
+
+use crate::{render::measure, separate_with, spaces, BuildDoc, DocAllocator, DocBuilder};
+
+/// Horizontal alignment of the cells in a column of a `Table`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Alignment {
+    Left,
+    Right,
+    Center,
+}
+
+/// Builder for a table of documents. Created by `DocAllocator::table`.
+pub struct Table<'a, D, A>
+where
+    D: ?Sized + DocAllocator<'a, A>,
+{
+    allocator: &'a D,
+    rows: Vec<Vec<D::Doc>>,
+    alignments: Vec<Alignment>,
+    separator: &'a str,
+    stacked_nest: isize,
+}
+
+impl<'a, D, A> Table<'a, D, A>
+where
+    D: ?Sized + DocAllocator<'a, A>,
+    A: 'a,
+{
+    pub(crate) fn new(allocator: &'a D, rows: Vec<Vec<D::Doc>>) -> Self {
+        Table {
+            allocator,
+            rows,
+            alignments: Vec::new(),
+            separator: " ",
+            stacked_nest: 4,
+        }
+    }
+
+    /// Sets the alignment of the cells in `column` (default: `Alignment::Left`).
+    #[inline]
+    pub fn align(mut self, column: usize, alignment: Alignment) -> Self {
+        if self.alignments.len() <= column {
+            self.alignments.resize(column + 1, Alignment::Left);
+        }
+        self.alignments[column] = alignment;
+        self
+    }
+
+    /// Sets the text placed between the columns (default: a single space).
+    #[inline]
+    pub fn separator(mut self, separator: &'a str) -> Self {
+        self.separator = separator;
+        self
+    }
+
+    /// Sets the indentation of the remaining cells of a row when the table does not fit and each
+    /// cell is put on its own line (default: 4).
+    #[inline]
+    pub fn stacked_nest(mut self, offset: isize) -> Self {
+        self.stacked_nest = offset;
+        self
+    }
+
+    /// Lays out the table.
+    ///
+    /// Every cell is measured by rendering it on its own with an unlimited width, so cells are
+    /// expected to fit on a single line. If the widest row does not fit in the remaining width the
+    /// table is instead laid out as a list where the first cell of each row is followed by the
+    /// other cells, each on its own line.
+    pub fn build(self) -> DocBuilder<'a, D, A>
+    where
+        D::Doc: Clone,
+    {
+        let Table {
+            allocator,
+            rows,
+            alignments,
+            separator,
+            stacked_nest,
+        } = self;
+
+        if rows.len() <= 1 && rows.first().map_or(0, |row| row.len()) <= 1 {
+            return match rows.into_iter().flatten().next() {
+                Some(cell) => DocBuilder(allocator, BuildDoc::DocPtr(cell)),
+                None => allocator.nil(),
+            };
+        }
+
+        let cell_widths = rows
+            .iter()
+            .map(|row| row.iter().map(|cell| measure(cell)).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let mut column_widths = Vec::new();
+        for row in &cell_widths {
+            if column_widths.len() < row.len() {
+                column_widths.resize(row.len(), 0);
+            }
+            for (column_width, &width) in column_widths.iter_mut().zip(row) {
+                *column_width = (*column_width).max(width);
+            }
+        }
+
+        let mut table_width = 0;
+        let aligned_rows = rows.iter().zip(&cell_widths).map(|(row, widths)| {
+            let mut doc = allocator.nil();
+            let mut row_width = 0;
+            for (i, (cell, &width)) in row.iter().zip(widths).enumerate() {
+                if i != 0 {
+                    doc = doc.append(separator);
+                    row_width += separator.len();
+                }
+                let padding = column_widths[i] - width;
+                let (before, after) = match alignments.get(i).cloned().unwrap_or(Alignment::Left) {
+                    Alignment::Left => (0, padding),
+                    Alignment::Right => (padding, 0),
+                    Alignment::Center => (padding / 2, padding - padding / 2),
+                };
+                // Avoid trailing whitespace after the last cell
+                let after = if i + 1 == row.len() { 0 } else { after };
+                doc = doc
+                    .append(spaces(allocator, before))
+                    .append(BuildDoc::DocPtr(cell.clone()))
+                    .append(spaces(allocator, after));
+                row_width += before + width + after;
+            }
+            table_width = table_width.max(row_width);
+            doc
+        });
+        let aligned = separate_with(allocator, aligned_rows, || allocator.hardline());
+
+        let stacked = separate_with(
+            allocator,
+            rows.into_iter().map(|row| {
+                let mut cells = row.into_iter();
+                let first = cells.next().map_or_else(
+                    || allocator.nil(),
+                    |cell| DocBuilder(allocator, BuildDoc::DocPtr(cell)),
+                );
+                let rest = cells.fold(allocator.nil(), |doc, cell| {
+                    doc.append(allocator.hardline())
+                        .append(BuildDoc::DocPtr(cell))
+                });
+                first.append(rest.nest(stacked_nest))
+            }),
+            || allocator.hardline(),
+        );
+
+        let aligned = aligned.into_doc();
+        let stacked = stacked.into_doc();
+        allocator.column(move |column| {
+            let aligned = aligned.clone();
+            let stacked = stacked.clone();
+            allocator
+                .page_width(move |page_width| {
+                    let doc = if column + table_width <= page_width {
+                        aligned.clone()
+                    } else {
+                        stacked.clone()
+                    };
+                    // The rows start at the column of the table, which may be in the middle of a
+                    // line, like `align`
+                    allocator
+                        .nesting(move |nesting| {
+                            DocBuilder(allocator, BuildDoc::DocPtr(doc.clone()))
+                                .nest(column as isize - nesting as isize)
+                                .into_doc()
+                        })
+                        .into_doc()
+                })
+                .into_doc()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Alignment, Arena, DocAllocator};
+
+    macro_rules! test {
+        ($size:expr, $actual:expr, $expected:expr) => {
+            let mut s = String::new();
+            $actual.render_fmt($size, &mut s).unwrap();
+            difference::assert_diff!(&s, $expected, "\n", 0);
+        };
+    }
+
+    #[test]
+    fn aligned_columns() {
+        let arena = Arena::<()>::new();
+        let doc = arena
+            .table(vec![
+                vec!["name", "size", "kind"],
+                vec!["main", "12", "fn"],
+                vec!["SPACES", "4096", "const"],
+            ])
+            .align(1, Alignment::Right)
+            .align(2, Alignment::Center)
+            .separator(" | ")
+            .build();
+        test!(
+            80,
+            doc.1,
+            "name   | size | kind\nmain   |   12 |  fn\nSPACES | 4096 | const"
+        );
+    }
+
+    #[test]
+    fn cells_are_documents() {
+        let arena = Arena::<()>::new();
+        let doc = arena.text("options:").append(
+            arena
+                .hardline()
+                .append(
+                    arena
+                        .table(vec![
+                            vec![arena.text("-v"), arena.text("verbose")],
+                            vec![
+                                arena.text("--width").append(" <n>"),
+                                arena
+                                    .text("line")
+                                    .append(arena.line())
+                                    .append("width")
+                                    .group(),
+                            ],
+                        ])
+                        .separator("  ")
+                        .build(),
+                )
+                .nest(2),
+        );
+        test!(
+            80,
+            doc.1,
+            "options:\n  -v           verbose\n  --width <n>  line width"
+        );
+    }
+
+    #[test]
+    fn stacked_when_too_wide() {
+        let arena = Arena::<()>::new();
+        let doc = arena
+            .table(vec![vec!["a", "short"], vec!["bb", "a much longer cell"]])
+            .stacked_nest(2)
+            .build();
+        test!(21, doc.1, "a  short\nbb a much longer cell");
+        // The second row is too wide, so every row is stacked
+        test!(20, doc.1, "a\n  short\nbb\n  a much longer cell");
+    }
+
+    #[test]
+    fn ragged_and_degenerate_tables() {
+        let arena = Arena::<()>::new();
+        let doc = arena.table(vec![vec!["a", "b", "c"], vec!["dd"]]).build();
+        test!(80, doc.1, "a  b c\ndd");
+
+        test!(80, arena.table(Vec::<Vec<&str>>::new()).build().1, "");
+        test!(1, arena.table(vec![vec!["cell"]]).build().1, "cell");
+    }
+
+    #[test]
+    fn padded_cells() {
+        let arena = Arena::<()>::new();
+        let doc = arena
+            .table(vec![
+                vec![arena.text("a"), arena.text("1").align_right()],
+                vec![arena.text("bb"), arena.text("2").center()],
+            ])
+            .build();
+        test!(10, doc.1, "a        1\nbb  2");
+    }
+
+    #[test]
+    fn table_in_the_middle_of_a_line() {
+        let arena = Arena::<()>::new();
+        let doc = arena.text("vars: ").append(
+            arena
+                .table(vec![vec!["a", "1"], vec!["bbb", "2"]])
+                .stacked_nest(2)
+                .build(),
+        );
+        test!(80, doc.1, "vars: a   1\n      bbb 2");
+        test!(10, doc.1, "vars: a\n        1\n      bbb\n        2");
+    }
+}
@@ -0,0 +1,234 @@
+//! Reflowing of text following the Unicode line breaking algorithm (UAX #14).
+
+use std::{borrow::Cow, marker::PhantomData, ops::Range};
+
+use unicode_linebreak::linebreaks;
+
+use crate::{DocAllocator, DocBuilder};
+
+const SOFT_HYPHEN: char = '\u{ad}';
+
+/// Builder for a reflowed piece of text. Created by `DocAllocator::wrap`.
+pub struct Wrap<'a, D, A>
+where
+    D: ?Sized + DocAllocator<'a, A>,
+    A: 'a,
+{
+    allocator: &'a D,
+    text: Cow<'a, str>,
+    hyphenate: bool,
+    _marker: PhantomData<A>,
+}
+
+/// What separates a word from the next one
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Gap {
+    /// A space which may be turned into a line break
+    Space,
+    /// A line break opportunity without any space, as between two CJK ideographs
+    Break,
+    /// A soft hyphen, which is printed as a `-` if the line is broken
+    Hyphen,
+    /// No line break is allowed
+    Join,
+}
+
+impl<'a, D, A> Wrap<'a, D, A>
+where
+    D: ?Sized + DocAllocator<'a, A>,
+    A: 'a,
+{
+    pub(crate) fn new(allocator: &'a D, text: Cow<'a, str>) -> Self {
+        Wrap {
+            allocator,
+            text,
+            hyphenate: false,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Allows lines to be broken at soft hyphens (U+00AD), inserting a `-` at the end of the line.
+    /// Without this soft hyphens are removed from the text.
+    #[inline]
+    pub fn hyphenate(mut self) -> Self {
+        self.hyphenate = true;
+        self
+    }
+
+    /// Lays out the text, filling each line with as many words as fit.
+    pub fn build(self) -> DocBuilder<'a, D, A> {
+        let Wrap {
+            allocator,
+            text,
+            hyphenate,
+            ..
+        } = self;
+
+        let piece = |range: Range<usize>| match text {
+            Cow::Borrowed(s) => allocator.text(&s[range]),
+            Cow::Owned(ref s) => allocator.text(s[range].to_string()),
+        };
+
+        let mut doc = allocator.nil();
+        for (i, paragraph) in paragraphs(&text).into_iter().enumerate() {
+            if i != 0 {
+                doc = doc
+                    .append(allocator.hardline())
+                    .append(allocator.hardline());
+            }
+            let words = words(&text, paragraph, hyphenate);
+            let last = words.len().saturating_sub(1);
+            for (j, (word, gap)) in words.into_iter().enumerate() {
+                doc = doc.append(piece(word));
+                if j == last {
+                    break;
+                }
+                doc = doc.append(match gap {
+                    Gap::Space => allocator.softline(),
+                    Gap::Break => allocator.softline_(),
+                    Gap::Hyphen => allocator
+                        .text("-")
+                        .append(allocator.hardline())
+                        .flat_alt(allocator.nil())
+                        .group(),
+                    Gap::Join => allocator.nil(),
+                });
+            }
+        }
+        doc
+    }
+}
+
+/// Returns the ranges of the paragraphs in `text`, which are separated by blank lines
+fn paragraphs(text: &str) -> Vec<Range<usize>> {
+    let mut paragraphs = Vec::new();
+    let mut current: Option<Range<usize>> = None;
+    let mut start = 0;
+    for line in text.split_inclusive('\n') {
+        let end = start + line.len();
+        if line.trim().is_empty() {
+            paragraphs.extend(current.take());
+        } else {
+            let line_start = start + (line.len() - line.trim_start().len());
+            let line_end = start + line.trim_end().len();
+            match current {
+                Some(ref mut paragraph) => paragraph.end = line_end,
+                None => current = Some(line_start..line_end),
+            }
+        }
+        start = end;
+    }
+    paragraphs.extend(current);
+    paragraphs
+}
+
+/// Whether `c` is whitespace that is removed at a line break opportunity. Non-breaking spaces are
+/// not included since no line break is allowed after them.
+fn is_break_space(c: char) -> bool {
+    matches!(
+        c,
+        ' ' | '\t'
+            | '\n'
+            | '\r'
+            | '\u{b}'
+            | '\u{c}'
+            | '\u{85}'
+            | '\u{200b}'
+            | '\u{2028}'
+            | '\u{2029}'
+    )
+}
+
+/// Splits `paragraph` at its line break opportunities, returning each word together with what
+/// follows it
+fn words(text: &str, paragraph: Range<usize>, hyphenate: bool) -> Vec<(Range<usize>, Gap)> {
+    let offset = paragraph.start;
+    let paragraph = &text[paragraph];
+
+    let mut words: Vec<(Range<usize>, Gap)> = Vec::new();
+    let mut start = 0;
+    for (end, _) in linebreaks(paragraph) {
+        let segment = &paragraph[start..end];
+        let word = segment.trim_end_matches(is_break_space);
+        let (word, gap) = if word.len() != segment.len() {
+            (word, Gap::Space)
+        } else if let Some(word) = word.strip_suffix(SOFT_HYPHEN) {
+            (word, if hyphenate { Gap::Hyphen } else { Gap::Join })
+        } else {
+            (word, Gap::Break)
+        };
+
+        if word.is_empty() {
+            // Only whitespace, as after a line break inside the paragraph
+            if let Some(previous) = words.last_mut() {
+                if gap == Gap::Space {
+                    previous.1 = Gap::Space;
+                }
+            }
+        } else {
+            words.push((offset + start..offset + start + word.len(), gap));
+        }
+        start = end;
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Arena, DocAllocator};
+
+    macro_rules! test {
+        ($size:expr, $actual:expr, $expected:expr) => {
+            let mut s = String::new();
+            $actual.render_fmt($size, &mut s).unwrap();
+            difference::assert_diff!(&s, $expected, "\n", 0);
+        };
+    }
+
+    #[test]
+    fn wrap_words() {
+        let arena = Arena::<()>::new();
+        let doc = arena.wrap("The quick  brown\nfox jumps").build();
+        test!(80, doc.1, "The quick brown fox jumps");
+        test!(10, doc.1, "The quick\nbrown fox\njumps");
+    }
+
+    #[test]
+    fn wrap_respects_break_rules() {
+        let arena = Arena::<()>::new();
+        // No break before punctuation (even after a space) or after a non-breaking space
+        let doc = arena.wrap("width: 100\u{a0}% (approximately) !").build();
+        test!(1, doc.1, "width:\n100\u{a0}%\n(approximately) !");
+
+        // Breaks are allowed between ideographs and after hyphens
+        let doc = arena.wrap("日本語 well-known").build();
+        test!(6, doc.1, "日本\n語\nwell-\nknown");
+    }
+
+    #[test]
+    fn wrap_paragraphs() {
+        let arena = Arena::<()>::new();
+        let doc = arena
+            .wrap(String::from("\n  first\n  paragraph\n\n \n\nsecond one\n"))
+            .build();
+        test!(80, doc.1, "first paragraph\n\nsecond one");
+        test!(6, doc.1, "first\nparagraph\n\nsecond\none");
+    }
+
+    #[test]
+    fn wrap_soft_hyphens() {
+        let arena = Arena::<()>::new();
+        let text = "a hy\u{ad}phen\u{ad}ated word";
+        test!(
+            80,
+            arena.wrap(text).hyphenate().build().1,
+            "a hyphenated word"
+        );
+        test!(
+            7,
+            arena.wrap(text).hyphenate().build().1,
+            "a hy-\nphen-\nated\nword"
+        );
+        test!(7, arena.wrap(text).build().1, "a\nhyphenated\nword");
+    }
+}